@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use image::DynamicImage;
+use rust_bevy_game::biome::BiomeRegistry;
+use rust_bevy_game::block::BlockRegistry;
+use rust_bevy_game::generation::{create_terrain_generator, TerrainGeneratorKind};
+use rust_bevy_game::generation::noise_terrain::NoiseTerrainGenerator;
+use rust_bevy_game::generation::preview::{biome_preview, height_preview, PreviewArea};
+use rust_bevy_game::world_settings::{parse_list, parse_value, WorldSettings, WorldSettingsError};
+
+// Writes biomes.png and heights.png of the world seen from above, without opening a window, e.g.
+// cargo run --release --bin map_preview -- --seed 42 --size 512,512 --step 4 --out previews
+// Takes the same world settings as the game, plus --centre <x,z> (the spawn point by default), --size <width,depth>
+// in pixels, --step <n> columns per pixel, --heights <min,max> for black and white, and --out <dir>
+struct PreviewOptions {
+    centre: Option<(i32, i32)>,
+    size: (u32, u32),
+    step: i32,
+    heights: (i32, i32),
+    out: PathBuf,
+}
+
+fn parse_args() -> Result<(PreviewOptions, WorldSettings), WorldSettingsError> {
+    let mut options = PreviewOptions {
+        centre: None,
+        size: (512, 512),
+        step: 1,
+        heights: (0, 64),
+        out: PathBuf::from("."),
+    };
+
+    let mut world_args = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next()
+            .ok_or_else(|| WorldSettingsError::InvalidArgument(format!("{} is missing a value", flag)))?;
+        match flag.as_str() {
+            "--centre" => {
+                let [x, z] = parse_list::<i32, 2>(&flag, &value)?;
+                options.centre = Some((x, z));
+            },
+            "--size" => {
+                let [width, depth] = parse_list::<u32, 2>(&flag, &value)?;
+                options.size = (width, depth);
+            },
+            "--step" => options.step = parse_value(&flag, &value)?,
+            "--heights" => {
+                let [min, max] = parse_list::<i32, 2>(&flag, &value)?;
+                options.heights = (min, max);
+            },
+            "--out" => options.out = PathBuf::from(value),
+            _ => world_args.extend([flag, value]),
+        }
+    }
+
+    if options.size.0 == 0 || options.size.1 == 0 || options.step <= 0 {
+        return Err(WorldSettingsError::InvalidArgument("size and step must be positive".to_string()));
+    }
+    if options.heights.1 <= options.heights.0 {
+        return Err(WorldSettingsError::InvalidArgument("the heights must go from low to high".to_string()));
+    }
+
+    Ok((options, WorldSettings::from_args(world_args)?))
+}
+
+fn main() {
+    let (options, world_settings) = match parse_args() {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let block_registry = BlockRegistry::default();
+    let biome_registry = match BiomeRegistry::load_dir(&world_settings.biomes_dir, &block_registry) {
+        Ok(biome_registry) => Arc::new(biome_registry),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let terrain_generator = match create_terrain_generator(&world_settings, biome_registry.clone()) {
+        Ok(terrain_generator) => terrain_generator,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let (x, z) = options.centre
+        .unwrap_or((world_settings.spawn_point.x as i32, world_settings.spawn_point.z as i32));
+    let area = PreviewArea::around(x, z, options.size.0, options.size.1, options.step);
+
+    if let Err(error) = std::fs::create_dir_all(&options.out) {
+        eprintln!("could not create {}: {}", options.out.display(), error);
+        std::process::exit(1);
+    }
+
+    let mut images: Vec<(&str, DynamicImage)> = vec![
+        ("heights.png", DynamicImage::ImageLuma8(height_preview(terrain_generator.as_ref(), &area, options.heights.0, options.heights.1))),
+    ];
+    // Only the noise generator has biomes
+    if world_settings.generator == TerrainGeneratorKind::Noise {
+        let generator = NoiseTerrainGenerator::new(world_settings.seed, biome_registry);
+        images.push(("biomes.png", DynamicImage::ImageRgb8(biome_preview(&generator, &area))));
+    }
+
+    for (file_name, image) in images {
+        let path = options.out.join(file_name);
+        if let Err(error) = image.save(&path) {
+            eprintln!("could not write {}: {}", path.display(), error);
+            std::process::exit(1);
+        }
+        println!("Wrote {}", path.display());
+    }
+}
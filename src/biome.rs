@@ -1,100 +1,449 @@
-use crate::biome::BiomeType::{Flat, PerlinMountains, Quarry};
+use std::fmt;
+use std::path::Path;
+use serde::Deserialize;
+use crate::block::BlockRegistry;
+use crate::generation::fractal_noise::{DomainWarp, FractalKind, FractalNoise};
+use crate::voxel::BlockId;
 
-#[derive(Copy, Clone)]
-pub enum BiomeType {
-    PerlinMountains,
-    Flat,
-    Quarry,
+// How a biome's noise becomes terrain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BiomeShape {
+    // 3D noise, solid where the noise is above a threshold that rises with height. Gives overhangs
+    Density,
+    // 2D noise gives the height of the ground in each column
+    Heightmap,
 }
 
-struct BiomeParam {
-    pub biome_type: BiomeType,
-    pub biome_chance: f64,
+// How quickly a density biome's threshold rises between min_height and max_height
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BiomeFalloff {
+    Linear,
+    // Rises quickly near the bottom, so most of the range is tall thin peaks
+    Log,
 }
 
+// A biome as written in assets/biomes/*.toml
+#[derive(Clone, Debug, Deserialize)]
+pub struct BiomeDefinition {
+    pub name: String,
+    // Relative size of the biome's area on the climate diagram
+    pub weight: f64,
+    // Where the biome sits on the climate diagram, both 0 to 1. Biomes border the biomes closest to them
+    pub temperature: f64,
+    pub humidity: f64,
+    pub shape: BiomeShape,
+    #[serde(default = "default_falloff")]
+    pub falloff: BiomeFalloff,
+    pub min_height: i32,
+    pub max_height: i32,
+    pub noise_scale: f64,
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+    #[serde(default = "default_noise")]
+    pub noise: FractalKind,
+    #[serde(default = "default_lacunarity")]
+    pub lacunarity: f64,
+    #[serde(default = "default_persistence")]
+    pub persistence: f64,
+    #[serde(default)]
+    pub seed_offset: f64,
+    // In voxels, 0 leaves the noise unwarped
+    #[serde(default)]
+    pub warp_amplitude: f64,
+    #[serde(default = "default_warp_scale")]
+    pub warp_scale: f64,
+    // The ground is top_block where it meets the air, subsurface_block for subsurface_depth voxels under that, then
+    // base_block all the way down
+    pub top_block: String,
+    pub subsurface_block: String,
+    pub subsurface_depth: u32,
+    #[serde(default = "default_base_block")]
+    pub base_block: String,
+    // Red, green and blue of the biome on map previews. Left out, a colour is picked from the name
+    pub map_color: Option<[u8; 3]>,
+}
+
+fn default_falloff() -> BiomeFalloff {
+    BiomeFalloff::Linear
+}
+
+fn default_octaves() -> u32 {
+    1
+}
+
+fn default_noise() -> FractalKind {
+    FractalKind::Fbm
+}
+
+fn default_lacunarity() -> f64 {
+    2.0
+}
+
+fn default_persistence() -> f64 {
+    0.5
+}
+
+fn default_warp_scale() -> f64 {
+    0.01
+}
+
+fn default_base_block() -> String {
+    "stone".to_string()
+}
+
+// A loaded biome, with its surface block names resolved against the block registry
+#[derive(Clone, Debug)]
+pub struct Biome {
+    pub name: String,
+    pub weight: f64,
+    pub temperature: f64,
+    pub humidity: f64,
+    pub shape: BiomeShape,
+    pub falloff: BiomeFalloff,
+    pub min_height: i32,
+    pub max_height: i32,
+    pub noise: FractalNoise,
+    pub top_block: BlockId,
+    pub subsurface_block: BlockId,
+    pub subsurface_depth: u32,
+    pub base_block: BlockId,
+    pub map_color: [u8; 3],
+}
+
+impl Biome {
+    // depth is how many ground voxels are above this one, 0 at the surface
+    pub fn block_for_depth(&self, depth: u32) -> BlockId {
+        if depth == 0 {
+            self.top_block
+        } else if depth <= self.subsurface_depth {
+            self.subsurface_block
+        } else {
+            self.base_block
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BiomeId(pub usize);
+
+#[derive(Clone, Copy, Debug)]
 pub struct BiomeStrength {
-    pub biome_type: BiomeType,
+    pub biome: BiomeId,
     pub biome_strength: f64,
 }
 
-const BIOME_PARAMS_ARR: [BiomeParam; 3] = [
-    BiomeParam {
-        biome_type: PerlinMountains,
-        biome_chance: 0.5,
-    },
-    BiomeParam {
-        biome_type: Flat,
-        biome_chance: 0.5,
-    },
-    BiomeParam {
-        biome_type: Quarry,
-        biome_chance: 0.5,
+#[derive(Debug)]
+pub enum BiomeError {
+    Io(std::io::Error),
+    Parse { file: String, error: toml::de::Error },
+    Invalid { biome: String, reason: String },
+    UnknownBlock { biome: String, block: String },
+    NoBiomes,
+}
+
+impl fmt::Display for BiomeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BiomeError::Io(error) => write!(f, "could not read biomes: {}", error),
+            BiomeError::Parse { file, error } => write!(f, "could not parse biome {}: {}", file, error),
+            BiomeError::Invalid { biome, reason } => write!(f, "biome {} is invalid: {}", biome, reason),
+            BiomeError::UnknownBlock { biome, block } => write!(f, "biome {} uses unknown block {}", biome, block),
+            BiomeError::NoBiomes => write!(f, "no biomes were defined"),
+        }
     }
+}
+
+impl std::error::Error for BiomeError {}
+
+// A light colour that stays the same for the same name, FNV-1a of the name split into three channels
+fn name_color(name: &str) -> [u8; 3] {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3));
+    [64 + (hash & 0xbf) as u8, 64 + (hash >> 8 & 0xbf) as u8, 64 + (hash >> 16 & 0xbf) as u8]
+}
+
+// The biomes shipped in assets/biomes, built in so tests and tools work without the assets directory
+const BUILTIN_BIOMES: [(&str, &str); 5] = [
+    ("desert.toml", include_str!("../assets/biomes/desert.toml")),
+    ("flat.toml", include_str!("../assets/biomes/flat.toml")),
+    ("perlin_mountains.toml", include_str!("../assets/biomes/perlin_mountains.toml")),
+    ("quarry.toml", include_str!("../assets/biomes/quarry.toml")),
+    ("snow.toml", include_str!("../assets/biomes/snow.toml")),
 ];
 
-pub fn get_random_biome(rand_val: f64) -> [BiomeStrength; 3] {
-    let mut max_biome_chance = 0.0;
+// How much further away on the climate diagram than the closest biome another biome can be and still blend in
+const CLIMATE_BLEND_DISTANCE: f64 = 0.08;
 
-    for i in 0..BIOME_PARAMS_ARR.len() {
-        max_biome_chance += BIOME_PARAMS_ARR[i].biome_chance;
+pub struct BiomeRegistry {
+    biomes: Vec<Biome>,
+}
+
+impl BiomeRegistry {
+    pub fn builtin(block_registry: &BlockRegistry) -> BiomeRegistry {
+        let definitions = BUILTIN_BIOMES.iter()
+            .map(|(file, toml_str)| parse_biome(file, toml_str))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        BiomeRegistry::from_definitions(definitions, block_registry).unwrap()
     }
 
-    let rand_val = rand_val * max_biome_chance;
+    // Loads every .toml file in the directory, in file name order
+    pub fn load_dir(dir: &Path, block_registry: &BlockRegistry) -> Result<BiomeRegistry, BiomeError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(BiomeError::Io)? {
+            let path = entry.map_err(BiomeError::Io)?.path();
+            if path.extension().is_some_and(|extension| extension == "toml") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut definitions = Vec::new();
+        for path in paths {
+            let toml_str = std::fs::read_to_string(&path).map_err(BiomeError::Io)?;
+            definitions.push(parse_biome(&path.display().to_string(), &toml_str)?);
+        }
+
+        BiomeRegistry::from_definitions(definitions, block_registry)
+    }
 
-    let mut prev_sum = 0.0;
-    let mut sum = 0.0;
+    pub fn from_definitions(definitions: Vec<BiomeDefinition>, block_registry: &BlockRegistry) -> Result<BiomeRegistry, BiomeError> {
+        if definitions.is_empty() {
+            return Err(BiomeError::NoBiomes);
+        }
 
-    for current_biome in 0..BIOME_PARAMS_ARR.len() as i32 {
-        sum += BIOME_PARAMS_ARR[current_biome as usize].biome_chance;
-        if rand_val <= sum {
-            let mut prev_biome = current_biome - 1;
-            if prev_biome < 0 {
-                prev_biome = BIOME_PARAMS_ARR.len() as i32 - 1;
+        let mut biomes = Vec::new();
+        for definition in definitions {
+            let invalid = |reason: &str| BiomeError::Invalid { biome: definition.name.clone(), reason: reason.to_string() };
+            if definition.weight <= 0.0 {
+                return Err(invalid("weight must be positive"));
             }
-            let mut next_biome = current_biome + 1;
-            if next_biome >= BIOME_PARAMS_ARR.len() as i32 {
-                next_biome = 0;
+            if !(0.0..=1.0).contains(&definition.temperature) || !(0.0..=1.0).contains(&definition.humidity) {
+                return Err(invalid("temperature and humidity must be from 0 to 1"));
+            }
+            if definition.max_height <= definition.min_height {
+                return Err(invalid("max_height must be above min_height"));
+            }
+            if definition.octaves == 0 {
+                return Err(invalid("octaves must be at least 1"));
+            }
+            if definition.lacunarity <= 0.0 || definition.persistence <= 0.0 {
+                return Err(invalid("lacunarity and persistence must be positive"));
+            }
+
+            let block = |name: &String| block_registry.id_by_name(name)
+                .ok_or_else(|| BiomeError::UnknownBlock { biome: definition.name.clone(), block: name.clone() });
+            let top_block = block(&definition.top_block)?;
+            let subsurface_block = block(&definition.subsurface_block)?;
+            let base_block = block(&definition.base_block)?;
+
+            let mut noise = FractalNoise::new(definition.noise, definition.noise_scale, definition.octaves)
+                .with_seed_offset(definition.seed_offset);
+            noise.lacunarity = definition.lacunarity;
+            noise.persistence = definition.persistence;
+            if definition.warp_amplitude > 0.0 {
+                noise = noise.with_warp(DomainWarp { amplitude: definition.warp_amplitude, scale: definition.warp_scale, octaves: 2 });
             }
 
-            // prev_sum = 0.5
-            // sum = 1.0
-            // rand_val = 0.75 (max strength because centre of biome)
-            // desired: strength_val = 1
-            // rand_val - prev_sum = 0.25
-            // sum - prev_sum = 0.5
-            // bs = (sum - prev_sum)/2 = 0.25
-            // rand_val = 0.75: bs - |(rand_val - prev_sum) - bs| = 0.25 - 0.25 = 0 = 0.25/bs = 1
-            // rand_val = 0.9:  bs - |(rand_val - prev_sum) - bs| = 0.4 - 0.25 = 0.15 = 0.1
-            // rand_val = 0.6:  bs - |(rand_val - prev_sum) - bs| = 0.1 - 0.25 = 0.15 = 0.1
-            let bs = (sum - prev_sum) / 2.0;
-            let current_biome_strength = (bs - ((rand_val - prev_sum) - bs).abs())/bs;
-            let mut next_biome_strength = 0.0;
-            let mut prev_biome_strength = 0.0;
-
-            if current_biome_strength < 0.7 {
-                // Factor in other biome strength
-                // TODO
-                next_biome_strength = (1.0 - current_biome_strength)/2.0;
-                prev_biome_strength = (1.0 - current_biome_strength)/2.0;
+            let map_color = definition.map_color.unwrap_or_else(|| name_color(&definition.name));
+            biomes.push(Biome {
+                name: definition.name,
+                weight: definition.weight,
+                temperature: definition.temperature,
+                humidity: definition.humidity,
+                shape: definition.shape,
+                falloff: definition.falloff,
+                min_height: definition.min_height,
+                max_height: definition.max_height,
+                noise,
+                top_block,
+                subsurface_block,
+                subsurface_depth: definition.subsurface_depth,
+                base_block,
+                map_color,
+            });
+        }
+
+        Ok(BiomeRegistry { biomes })
+    }
+
+    pub fn get(&self, biome_id: BiomeId) -> &Biome {
+        &self.biomes[biome_id.0]
+    }
+
+    pub fn id_by_name(&self, name: &str) -> Option<BiomeId> {
+        self.biomes.iter().position(|biome| biome.name == name).map(BiomeId)
+    }
+
+    pub fn biomes(&self) -> &[Biome] {
+        &self.biomes
+    }
+
+    // Nothing is generated at or above this height
+    pub fn max_height(&self) -> i32 {
+        self.biomes.iter().map(|biome| biome.max_height).max().unwrap()
+    }
+
+    pub fn max_subsurface_depth(&self) -> u32 {
+        self.biomes.iter().map(|biome| biome.subsurface_depth).max().unwrap()
+    }
+
+    // The biomes at a point on the climate diagram, strongest first. Each biome owns the part of the diagram closer to
+    // it than any other, distances divided by weight so heavier biomes cover more. Biomes within
+    // CLIMATE_BLEND_DISTANCE of the closest blend in. The strengths add up to 1 and are continuous, at the edge
+    // between two biomes both are 0.5. Unused entries have a strength of 0
+    pub fn biome_strengths(&self, temperature: f64, humidity: f64) -> [BiomeStrength; 3] {
+        let mut distances: Vec<(usize, f64)> = self.biomes.iter().enumerate()
+            .map(|(id, biome)| (id, self.climate_distance(biome, temperature, humidity)))
+            .collect();
+        distances.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let closest = distances[0].1;
+        let blend = |distance: f64| (1.0 - (distance - closest) / CLIMATE_BLEND_DISTANCE).max(0.0).powi(2);
+
+        // Only three biomes are kept, so take off the blend of the fourth closest. Otherwise a biome would jump
+        // from its blend to nothing as it swapped places with the fourth
+        let cutoff = distances.get(3).map_or(0.0, |(_, distance)| blend(*distance));
+
+        let mut strengths = [BiomeStrength { biome: BiomeId(distances[0].0), biome_strength: 0.0 }; 3];
+        for (i, (id, distance)) in distances.iter().take(3).enumerate() {
+            strengths[i] = BiomeStrength { biome: BiomeId(*id), biome_strength: (blend(*distance) - cutoff).max(0.0) };
+        }
+
+        let total: f64 = strengths.iter().map(|s| s.biome_strength).sum();
+        if total > 0.0 {
+            strengths.iter_mut().for_each(|s| s.biome_strength /= total);
+        } else {
+            // Four biomes exactly as close, which only happens at single points
+            strengths[0].biome_strength = 1.0;
+        }
+        strengths
+    }
+
+    fn climate_distance(&self, biome: &Biome, temperature: f64, humidity: f64) -> f64 {
+        let dt = biome.temperature - temperature;
+        let dh = biome.humidity - humidity;
+        (dt * dt + dh * dh).sqrt() / biome.weight
+    }
+
+    // The climate diagram drawn with the first letter of the strongest biome, temperature increasing to the right
+    // and humidity increasing upwards. Handy for checking how biome files lay out
+    pub fn climate_diagram(&self, width: usize, height: usize) -> String {
+        let mut diagram = String::new();
+        for row in (0..height).rev() {
+            let humidity = (row as f64 + 0.5) / height as f64;
+            for column in 0..width {
+                let temperature = (column as f64 + 0.5) / width as f64;
+                let biome = self.get(self.biome_strengths(temperature, humidity)[0].biome);
+                diagram.push(biome.name.chars().next().unwrap_or('?'));
             }
+            diagram.push('\n');
+        }
+        diagram
+    }
+}
 
-            return [
-                BiomeStrength {
-                    biome_type: BIOME_PARAMS_ARR[current_biome as usize].biome_type,
-                    biome_strength: current_biome_strength
-                },
-                BiomeStrength {
-                    biome_type: BIOME_PARAMS_ARR[next_biome as usize].biome_type,
-                    biome_strength: next_biome_strength
-                },
-                BiomeStrength {
-                    biome_type: BIOME_PARAMS_ARR[prev_biome as usize].biome_type,
-                    biome_strength: prev_biome_strength
+fn parse_biome(file: &str, toml_str: &str) -> Result<BiomeDefinition, BiomeError> {
+    toml::from_str(toml_str).map_err(|error| BiomeError::Parse { file: file.to_string(), error })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::biome::{BiomeError, BiomeRegistry, BiomeStrength, parse_biome};
+    use crate::block;
+    use crate::block::BlockRegistry;
+
+    #[test]
+    fn biome_strengths_are_continuous_test() {
+        let biome_registry = BiomeRegistry::builtin(&BlockRegistry::default());
+        let strength_of = |strengths: &[BiomeStrength; 3], biome: usize| -> f64 {
+            strengths.iter().filter(|s| s.biome.0 == biome).map(|s| s.biome_strength).sum()
+        };
+
+        // Walk rows and columns of the climate diagram, comparing each biome wherever it is in the array
+        for line in 0..=20 {
+            let line = line as f64 / 20.0;
+            let mut previous = [biome_registry.biome_strengths(0.0, line), biome_registry.biome_strengths(line, 0.0)];
+            for i in 1..=1000 {
+                let step = i as f64 / 1000.0;
+                let current = [biome_registry.biome_strengths(step, line), biome_registry.biome_strengths(line, step)];
+                for (strengths, previous) in current.iter().zip(previous.iter()) {
+                    let total: f64 = strengths.iter().map(|s| s.biome_strength).sum();
+                    assert!((total - 1.0).abs() < 1e-9);
+                    assert!(strengths[0].biome_strength >= strengths[1].biome_strength);
+                    for biome in 0..biome_registry.biomes().len() {
+                        assert!((strength_of(strengths, biome) - strength_of(previous, biome)).abs() < 0.05);
+                    }
                 }
-            ]
+                previous = current;
+            }
+        }
+    }
+
+    #[test]
+    fn desert_never_borders_snow_test() {
+        let biome_registry = BiomeRegistry::builtin(&BlockRegistry::default());
+        let desert = biome_registry.id_by_name("desert").unwrap();
+        let snow = biome_registry.id_by_name("snow").unwrap();
+
+        for t in 0..=200 {
+            for h in 0..=200 {
+                let strengths = biome_registry.biome_strengths(t as f64 / 200.0, h as f64 / 200.0);
+                let has = |biome| strengths.iter().any(|s| s.biome == biome && s.biome_strength > 0.0);
+                assert!(!(has(desert) && has(snow)), "desert and snow blend at {} {}", t, h);
+            }
         }
-        prev_sum = sum;
+
+        let diagram = biome_registry.climate_diagram(40, 20);
+        assert!(diagram.contains('d') && diagram.contains('s'));
     }
 
-    panic!("AHHHHHH")
-}
\ No newline at end of file
+    #[test]
+    fn shipped_biome_files_load_test() {
+        let block_registry = BlockRegistry::default();
+        let loaded = BiomeRegistry::load_dir(Path::new("assets/biomes"), &block_registry).unwrap();
+        let builtin = BiomeRegistry::builtin(&block_registry);
+        assert_eq!(loaded.biomes().len(), builtin.biomes().len());
+
+        let flat = builtin.get(builtin.id_by_name("flat").unwrap());
+        assert_eq!(flat.block_for_depth(0), block::GRASS);
+        assert_eq!(flat.block_for_depth(flat.subsurface_depth), block::DIRT);
+        assert_eq!(flat.block_for_depth(flat.subsurface_depth + 1), block::STONE);
+    }
+
+    #[test]
+    fn invalid_biome_test() {
+        let block_registry = BlockRegistry::default();
+        let biome = |surface_block: &str, weight: f64| parse_biome("test.toml", &format!(r#"
+            name = "test"
+            weight = {}
+            temperature = 0.5
+            humidity = 0.5
+            shape = "heightmap"
+            min_height = 0
+            max_height = 10
+            noise_scale = 0.1
+            top_block = "{}"
+            subsurface_block = "dirt"
+            subsurface_depth = 2
+        "#, weight, surface_block)).unwrap();
+
+        assert!(BiomeRegistry::from_definitions(vec![biome("dirt", 1.0)], &block_registry).is_ok());
+        assert!(matches!(BiomeRegistry::from_definitions(vec![biome("cheese", 1.0)], &block_registry),
+                         Err(BiomeError::UnknownBlock { .. })));
+        assert!(matches!(BiomeRegistry::from_definitions(vec![biome("dirt", 0.0)], &block_registry),
+                         Err(BiomeError::Invalid { .. })));
+        let mut flat_noise = biome("dirt", 1.0);
+        flat_noise.persistence = 0.0;
+        assert!(matches!(BiomeRegistry::from_definitions(vec![flat_noise], &block_registry),
+                         Err(BiomeError::Invalid { .. })));
+        let mut too_hot = biome("dirt", 1.0);
+        too_hot.temperature = 1.5;
+        assert!(matches!(BiomeRegistry::from_definitions(vec![too_hot], &block_registry),
+                         Err(BiomeError::Invalid { .. })));
+        assert!(matches!(BiomeRegistry::from_definitions(Vec::new(), &block_registry), Err(BiomeError::NoBiomes)));
+        assert!(parse_biome("test.toml", "name = \"missing fields\"").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::chunk_vertexes::QuadDirection;
+use crate::voxel::{BlockId, Voxel};
+
+// Built-in blocks, registered in this order by BlockRegistry::default()
+pub const AIR: BlockId = BlockId(0);
+pub const STONE: BlockId = BlockId(1);
+pub const DIRT: BlockId = BlockId(2);
+pub const GRASS: BlockId = BlockId(3);
+pub const LIGHT_GRASS: BlockId = BlockId(4);
+pub const DARK_GRASS: BlockId = BlockId(5);
+pub const SAND: BlockId = BlockId(6);
+pub const SNOW: BlockId = BlockId(7);
+pub const COAL_ORE: BlockId = BlockId(8);
+pub const IRON_ORE: BlockId = BlockId(9);
+pub const LOG: BlockId = BlockId(10);
+pub const LEAVES: BlockId = BlockId(11);
+pub const WATER: BlockId = BlockId(12);
+
+// Indexes into assets/atlas.png
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockFaceTextures {
+    pub top: i32,
+    pub bottom: i32,
+    pub side: i32,
+}
+
+impl BlockFaceTextures {
+    pub fn all(atlas_index: i32) -> BlockFaceTextures {
+        BlockFaceTextures {
+            top: atlas_index,
+            bottom: atlas_index,
+            side: atlas_index,
+        }
+    }
+
+    pub fn for_direction(&self, direction: QuadDirection) -> i32 {
+        match direction {
+            QuadDirection::TOP => self.top,
+            QuadDirection::BOTTOM => self.bottom,
+            _ => self.side
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockDefinition {
+    pub name: String,
+    // Whether the block is rendered and collides
+    pub solid: bool,
+    // Whether the block hides the faces of the blocks next to it
+    pub opaque: bool,
+    // Drawn in the chunk's separate, see through water mesh rather than with the terrain
+    pub liquid: bool,
+    pub textures: BlockFaceTextures,
+    pub hardness: f32,
+    pub light_emission: u8,
+}
+
+impl BlockDefinition {
+    pub fn new(name: &str, textures: BlockFaceTextures, hardness: f32) -> BlockDefinition {
+        BlockDefinition {
+            name: name.to_string(),
+            solid: true,
+            opaque: true,
+            liquid: false,
+            textures,
+            hardness,
+            light_emission: 0,
+        }
+    }
+}
+
+pub struct BlockRegistry {
+    blocks: Vec<BlockDefinition>,
+}
+
+impl BlockRegistry {
+    pub fn empty() -> BlockRegistry {
+        BlockRegistry { blocks: Vec::new() }
+    }
+
+    pub fn register(&mut self, block: BlockDefinition) -> BlockId {
+        assert!(self.id_by_name(&block.name).is_none(), "Block {} is already registered", block.name);
+        self.blocks.push(block);
+        BlockId((self.blocks.len() - 1) as u16)
+    }
+
+    pub fn get(&self, block_id: BlockId) -> &BlockDefinition {
+        &self.blocks[block_id.0 as usize]
+    }
+
+    pub fn get_voxel_block(&self, voxel: &Voxel) -> &BlockDefinition {
+        self.get(voxel.block_id)
+    }
+
+    pub fn id_by_name(&self, name: &str) -> Option<BlockId> {
+        self.blocks.iter()
+            .position(|block| block.name == name)
+            .map(|idx| BlockId(idx as u16))
+    }
+
+    pub fn is_solid(&self, voxel: &Voxel) -> bool {
+        self.get(voxel.block_id).solid
+    }
+
+    pub fn is_opaque(&self, voxel: &Voxel) -> bool {
+        self.get(voxel.block_id).opaque
+    }
+
+    pub fn is_liquid(&self, voxel: &Voxel) -> bool {
+        self.get(voxel.block_id).liquid
+    }
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        let mut registry = BlockRegistry::empty();
+
+        registry.register(BlockDefinition {
+            name: "air".to_string(),
+            solid: false,
+            opaque: false,
+            liquid: false,
+            textures: BlockFaceTextures::all(0),
+            hardness: 0.0,
+            light_emission: 0,
+        });
+        registry.register(BlockDefinition::new("stone", BlockFaceTextures::all(3), 1.5));
+        registry.register(BlockDefinition::new("dirt", BlockFaceTextures::all(4), 0.5));
+        registry.register(BlockDefinition::new("grass", BlockFaceTextures { top: 0, bottom: 4, side: 4 }, 0.6));
+        registry.register(BlockDefinition::new("light_grass", BlockFaceTextures { top: 1, bottom: 4, side: 4 }, 0.6));
+        registry.register(BlockDefinition::new("dark_grass", BlockFaceTextures { top: 2, bottom: 4, side: 4 }, 0.6));
+        registry.register(BlockDefinition::new("sand", BlockFaceTextures::all(5), 0.5));
+        registry.register(BlockDefinition::new("snow", BlockFaceTextures::all(6), 0.2));
+        registry.register(BlockDefinition::new("coal_ore", BlockFaceTextures::all(8), 3.0));
+        registry.register(BlockDefinition::new("iron_ore", BlockFaceTextures::all(9), 3.0));
+        registry.register(BlockDefinition::new("log", BlockFaceTextures { top: 11, bottom: 11, side: 10 }, 2.0));
+        registry.register(BlockDefinition::new("leaves", BlockFaceTextures::all(12), 0.2));
+        registry.register(BlockDefinition {
+            name: "water".to_string(),
+            solid: false,
+            opaque: false,
+            liquid: true,
+            textures: BlockFaceTextures::all(7),
+            hardness: 0.0,
+            light_emission: 0,
+        });
+
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::{AIR, BlockRegistry, COAL_ORE, DARK_GRASS, DIRT, GRASS, IRON_ORE, LEAVES, LIGHT_GRASS, LOG, SAND, SNOW, STONE, WATER};
+
+    #[test]
+    fn default_registry_matches_constants() {
+        let registry = BlockRegistry::default();
+        assert_eq!(registry.id_by_name("air"), Some(AIR));
+        assert_eq!(registry.id_by_name("stone"), Some(STONE));
+        assert_eq!(registry.id_by_name("dirt"), Some(DIRT));
+        assert_eq!(registry.id_by_name("grass"), Some(GRASS));
+        assert_eq!(registry.id_by_name("light_grass"), Some(LIGHT_GRASS));
+        assert_eq!(registry.id_by_name("dark_grass"), Some(DARK_GRASS));
+        assert_eq!(registry.id_by_name("sand"), Some(SAND));
+        assert_eq!(registry.id_by_name("snow"), Some(SNOW));
+        assert_eq!(registry.id_by_name("coal_ore"), Some(COAL_ORE));
+        assert_eq!(registry.id_by_name("iron_ore"), Some(IRON_ORE));
+        assert_eq!(registry.id_by_name("log"), Some(LOG));
+        assert_eq!(registry.id_by_name("leaves"), Some(LEAVES));
+        assert_eq!(registry.id_by_name("water"), Some(WATER));
+        assert!(!registry.get(AIR).solid);
+        assert!(registry.get(STONE).solid);
+        assert!(!registry.get(WATER).solid && !registry.get(WATER).opaque && registry.get(WATER).liquid);
+    }
+}
@@ -1,38 +1,24 @@
 use bevy::math::{IVec3, Vec3};
-use opensimplex_noise_rs::OpenSimplexNoise;
-use crate::biome::{BiomeStrength, get_random_biome};
-use crate::biome::BiomeType::{Flat, PerlinMountains, Quarry};
-use crate::chunk_utils::{voxel_index_to_xyz, xyz_to_voxel_index};
-use crate::Transform;
+use crate::block::BlockRegistry;
+use crate::chunk_border::ChunkBorder;
+use crate::chunk_storage::ChunkVoxels;
+use crate::chunk_utils::xyz_to_voxel_index;
+use crate::generation::pipeline::ChunkStatus;
+use bevy::prelude::Transform;
 use crate::voxel::Voxel;
 
+// Only voxel data, chunks are generated by the world's TerrainGenerator
+#[derive(Clone, Debug)]
 pub struct Chunk {
     pub location: IVec3,
     pub size: IVec3,
-    pub voxels: Vec<Voxel>,
-    pub noise_generator: OpenSimplexNoise,
-    // TODO maybe take Minecraft approach of 16x16x280 chunks
-}
-
-pub struct ChunkGenerationAttributes {
-    pub calculate_solid_probability: fn(x: f32, y: f32, z: f32) -> f32
-}
-
-fn sparse_chunk() -> ChunkGenerationAttributes {
-    ChunkGenerationAttributes {
-        calculate_solid_probability: |_x: f32, _y: f32, _z: f32| -> f32 {
-            return 0.3;
-        }
-    }
-}
-
-fn flat_chunk() -> ChunkGenerationAttributes {
-    ChunkGenerationAttributes {
-        calculate_solid_probability: |_x: f32, _y: f32, z: f32| -> f32 {
-            let c = 28.0; // The higher c is, the higher the probability that the higher voxels are solid
-            1.0 - (z / c)
-        }
-    }
+    pub voxels: ChunkVoxels,
+    // A copy of the neighbouring chunks' facing voxels, generated along with the chunk and kept in sync by VoxelWorld
+    pub border: ChunkBorder,
+    // Edited since its mesh was last built
+    pub dirty: bool,
+    // How far through the generation stages it is
+    pub status: ChunkStatus,
 }
 
 impl Chunk {
@@ -44,171 +30,179 @@ impl Chunk {
         ))
     }
 
-    pub fn noise(size: IVec3, location: IVec3) -> Chunk {
-        let mut voxels = Vec::new();
-
-        let noise_generator = OpenSimplexNoise::new(Some(883_279_212_983_182_319)); // if not provided, default seed is equal to 0
-        let offset = location * size;
-        let voxel_length = (size.x * size.y * size.z) as usize;
-
-        for n in 0..voxel_length {
-            let xyz = voxel_index_to_xyz(n as i32, &size);
-            let xyz_offset = offset + xyz;
-            voxels.push(generate_voxel_at_xyz(&noise_generator, &xyz_offset, &size));
+    // A chunk where every voxel is the same, e.g. for tests or placeholder chunks
+    pub fn filled(size: IVec3, location: IVec3, voxel: Voxel) -> Chunk {
+        Chunk {
+            voxels: ChunkVoxels::uniform((size.x * size.y * size.z) as usize, voxel),
+            border: ChunkBorder::filled(size, voxel),
+            size,
+            location,
+            dirty: false,
+            status: ChunkStatus::Empty,
         }
+    }
 
-        println!("Voxel Count is {}", voxel_length);
-
+    pub fn new(size: IVec3, location: IVec3, voxels: ChunkVoxels, border: ChunkBorder) -> Chunk {
         Chunk {
             voxels,
+            border,
             size,
             location,
-            noise_generator,
+            dirty: false,
+            status: ChunkStatus::Empty,
         }
     }
 
-    // Capable of generating voxels for different chunks, i.e. local_xyz = { -1, -1, -1 } is possible
-    pub fn generate_voxel_in_localspace(&self, local_xyz: &IVec3) -> Voxel {
-        let global_xyz = (self.location * self.size) + *local_xyz;
-        generate_voxel_at_xyz(&self.noise_generator, &global_xyz, &self.size)
+    pub fn get_voxel(&self, xyz: &IVec3) -> Voxel {
+        self.voxels.get(xyz_to_voxel_index(xyz, &self.size))
     }
 
-    pub fn get_voxel(&self, xyz: &IVec3) -> &Voxel {
-        return &self.voxels[xyz_to_voxel_index(&xyz, &self.size)];
+    pub fn contains(&self, xyz: &IVec3) -> bool {
+        xyz.cmpge(IVec3::ZERO).all() && xyz.cmplt(self.size).all()
     }
-}
 
-fn get_biome(noise_generator: &OpenSimplexNoise, global_xyz: &IVec3, _chunk_size: &IVec3) -> [BiomeStrength; 3] {
-    // let chunk_x = (global_xyz.x as f32/ chunk_size.x as f32).floor() as f64;
-    // let chunk_z = (global_xyz.z as f32 / chunk_size.z as f32).floor() as f64;
-
-    let noise = noise_generator.eval_2d(global_xyz.x as f64 / 100.0, global_xyz.z as f64 / 100.0);
-    // Normalise val from -1 to 1, to 0 to 1
-    let normalised_noise = (noise + 1.0) / 2.0;
-
-    return get_random_biome(normalised_noise);
-}
-
-fn perlin_mountains(_noise_generator: &OpenSimplexNoise, global_xyz: &IVec3) -> f64 {
-    // The chance of the voxel being solid, increases the lower y is
-    let chance = ((global_xyz.y) as f64).log10() / (64.0_f64).log10();
-    chance
-    //let solid = val as f64 > chance;
-    //solid
-}
-
-fn mc(noise_generator: &OpenSimplexNoise, global_xyz: &IVec3, min_height: i32, max_height: i32, scale: f64) -> bool {
-    if global_xyz.y <= min_height {
-        return true
-    } else if global_xyz.y >= max_height {
-        return false
+    // Also reads one voxel past each face from the border, i.e. local_xyz = { -1, 0, 0 } is possible
+    pub fn get_voxel_or_border(&self, xyz: &IVec3) -> Voxel {
+        if self.contains(xyz) {
+            self.get_voxel(xyz)
+        } else {
+            self.border.get(xyz)
+        }
     }
 
-    let noise = noise_generator.eval_2d(
-        (global_xyz.x) as f64 * scale,
-        (global_xyz.z) as f64 * scale);
-
-    // Normalise val from -1 to 1, to 0 to 1
-    let normalised_noise = (noise + 1.0) / 2.0;
+    // Returns the border voxel that was replaced, marks the chunk dirty if it changed as a face may now be visible
+    pub fn set_border_voxel(&mut self, xyz: &IVec3, voxel: Voxel) -> Voxel {
+        let previous = self.border.set(xyz, voxel);
+        if previous != voxel {
+            self.dirty = true;
+        }
+        previous
+    }
 
-    let offset = normalised_noise * (max_height - min_height) as f64;
+    // Returns the voxel that was replaced, marks the chunk dirty if it changed
+    pub fn set_voxel(&mut self, xyz: &IVec3, voxel: Voxel) -> Voxel {
+        let previous = self.voxels.set(xyz_to_voxel_index(xyz, &self.size), voxel);
+        if previous != voxel {
+            self.dirty = true;
+        }
+        previous
+    }
 
-    let solid = global_xyz.y < offset as i32;
-    solid
-}
+    // Writes either a voxel or, one past a face, a border voxel. Positions outside both are ignored and give None
+    pub fn set_voxel_or_border(&mut self, xyz: &IVec3, voxel: Voxel) -> Option<Voxel> {
+        if self.contains(xyz) {
+            Some(self.set_voxel(xyz, voxel))
+        } else if self.border.contains(xyz) {
+            Some(self.set_border_voxel(xyz, voxel))
+        } else {
+            None
+        }
+    }
 
-fn flat(noise_generator: &OpenSimplexNoise, global_xyz: &IVec3, min_height: i32, max_height: i32, scale: f64) -> bool {
-    if global_xyz.y <= min_height {
-        return true
-    } else if global_xyz.y >= max_height {
-        return false
+    pub fn is_on_border(&self, xyz: &IVec3) -> bool {
+        xyz.x == 0 || xyz.y == 0 || xyz.z == 0
+            || xyz.x == self.size.x - 1 || xyz.y == self.size.y - 1 || xyz.z == self.size.z - 1
     }
 
-    let mut val = noise_generator.eval_3d(
-        (global_xyz.x) as f64 * scale,
-        (global_xyz.y) as f64 * scale,
-        (global_xyz.z) as f64 * scale);
+    pub fn is_voxel_solid(&self, xyz: &IVec3, block_registry: &BlockRegistry) -> bool {
+        block_registry.is_solid(&self.get_voxel(xyz))
+    }
 
-    // Normalise val from -1 to 1, to 0 to 1
-    val = (val + 1.0) / 2.0;
+    pub fn is_uniform(&self) -> bool {
+        self.voxels.uniform_voxel().is_some()
+    }
 
-    // The chance of the voxel being solid, increases the lower y is
-    let height = (global_xyz.y as f32 - min_height as f32) / (max_height - min_height) as f32; // 0 - 1
+    // Drops unused palette entries, worth calling after a large batch of edits
+    pub fn compact(&mut self) {
+        self.voxels.compact();
+    }
 
-    let solid = val as f64 > height as f64;
-    solid
+    pub fn memory_usage(&self) -> usize {
+        self.voxels.memory_usage() + self.border.memory_usage()
+    }
 }
 
-// Generates the voxel at xyz, needs to also be able to generate voxels for neighbouring chunks
-fn generate_voxel_at_xyz(noise_generator: &OpenSimplexNoise, global_xyz: &IVec3, chunk_size: &IVec3) -> Voxel {
-    // global xyz means that voxel xyz is from 0..inf
-    // i.e. Chunk 0,0 xyz = 0..32
-    // Chunk 1,1 xyz = 32..64
-    // Chunk 10,10 xyz = 320..352
-
-    let biome_strengths = get_biome(noise_generator, global_xyz, chunk_size);
-    // TODO
-    // Make generation functions return a chance of solid
-    let biome = biome_strengths[0].biome_type;
-
-    let scale = 0.01;
-    let mut val = noise_generator.eval_3d(
-        (global_xyz.x) as f64 * scale,
-        (global_xyz.y) as f64 * scale,
-        (global_xyz.z) as f64 * scale);
-
-    // Normalise val from -1 to 1, to 0 to 1
-    val = (val + 1.0) / 2.0;
-
-    let mut voxels: [Voxel; 3] = [ Voxel { solid: false },Voxel { solid: false },Voxel { solid: false } ];
-    for i in 0..biome_strengths.len() {
-        voxels[i] = match biome {
-            PerlinMountains => {
-                let chance = perlin_mountains(noise_generator, global_xyz);
-                let solid = val as f64 > chance;
-                Voxel { solid }
-            },
-            Flat => {
-                Voxel {
-                    solid: flat(noise_generator, global_xyz, 8, 24, 0.07),
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::chunk::Chunk;
+    use bevy::math::IVec3;
+
+    // Every voxel and border voxel of the two chunks is the same
+    pub fn assert_same_chunk(expected: &Chunk, actual: &Chunk) {
+        assert_eq!(expected.location, actual.location);
+        for x in -1..=expected.size.x {
+            for y in -1..=expected.size.y {
+                for z in -1..=expected.size.z {
+                    let xyz = IVec3::new(x, y, z);
+                    if expected.contains(&xyz) || expected.border.contains(&xyz) {
+                        assert_eq!(actual.get_voxel_or_border(&xyz), expected.get_voxel_or_border(&xyz),
+                                   "chunk {} at {}", expected.location, xyz);
+                    }
                 }
-            },
-            Quarry => {
-                Voxel { solid: mc(noise_generator, global_xyz, 7, 64, 0.03) }
             }
-        };
+        }
     }
 
-    voxels[0]
-}
+    fn shares_face(chunk: &Chunk, neighbour: &Chunk) -> bool {
+        let offset = (neighbour.location - chunk.location).abs();
+        offset.x + offset.y + offset.z == 1
+    }
 
+    // Each chunk's border matches the voxels of the other, the chunks must share a face
+    pub fn assert_seamless(chunk: &Chunk, neighbour: &Chunk) {
+        assert!(shares_face(chunk, neighbour), "{} and {} don't share a face", chunk.location, neighbour.location);
+        let offset = neighbour.location - chunk.location;
+        for n in 0..chunk.size.x * chunk.size.y * chunk.size.z {
+            let xyz = IVec3::new(n % chunk.size.x, n / chunk.size.x % chunk.size.y, n / (chunk.size.x * chunk.size.y));
+            let outside = xyz + offset;
+            if chunk.contains(&outside) {
+                continue;
+            }
+            let neighbour_xyz = outside - offset * chunk.size;
+            assert_eq!(chunk.get_voxel_or_border(&outside), neighbour.get_voxel(&neighbour_xyz), "chunk {} at {}", chunk.location, outside);
+            assert_eq!(neighbour.get_voxel_or_border(&(xyz - offset * chunk.size)), chunk.get_voxel(&xyz),
+                       "chunk {} at {}", neighbour.location, xyz - offset * chunk.size);
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use opensimplex_noise_rs::OpenSimplexNoise;
-    use crate::chunk::generate_voxel_at_xyz;
-    use crate::chunk_utils::voxel_index_to_xyz;
-    use crate::IVec3;
+    // Loads the chunks forwards, backwards and shuffled, and checks they come out the same whatever the order and
+    // match across every shared face. Returns the chunks in order of location
+    pub fn assert_load_order_independent<F: Fn(&[IVec3]) -> Vec<Chunk>>(chunk_locations: &[IVec3], load: F) -> Vec<Chunk> {
+        let mut orders = vec![chunk_locations.to_vec()];
+        orders.push(chunk_locations.iter().rev().copied().collect());
+        let mut shuffled = chunk_locations.to_vec();
+        shuffled.sort_by_key(|c| (c.y, c.z * 7 + c.x * 3));
+        orders.push(shuffled);
+
+        let mut loads: Vec<Vec<Chunk>> = orders.iter()
+            .map(|order| {
+                let mut chunks = load(order);
+                chunks.sort_by_key(|chunk| chunk.location.to_array());
+                chunks
+            })
+            .collect();
+        let chunks = loads.remove(0);
+        assert_eq!(chunks.len(), chunk_locations.len());
+        for other in loads {
+            assert_eq!(other.len(), chunks.len());
+            for (expected, actual) in chunks.iter().zip(other.iter()) {
+                assert_same_chunk(expected, actual);
+            }
+        }
 
-    #[test]
-    fn generate_voxel_at_xyz_test() {
-        let noise_generator = OpenSimplexNoise::new(Some(883_279_212_983_182_319));
-        let actual = generate_voxel_at_xyz(&noise_generator, &IVec3::new(0, 0, 0));
-        assert_eq!(generate_voxel_at_xyz(&noise_generator, &IVec3::new(0, 0, 0)), actual);
-
-        let noise_generator2 = OpenSimplexNoise::new(Some(883_279_212_983_182_319));
-        assert_eq!(generate_voxel_at_xyz(&noise_generator2, &IVec3::new(0, 0, 0)), actual);
-
-        for x in -3..67 {
-            for y in -3..67 {
-                for z in -3..67 {
-                    assert_eq!(
-                        generate_voxel_at_xyz(&noise_generator, &IVec3::new(x, y, z)),
-                        generate_voxel_at_xyz(&noise_generator2, &IVec3::new(x, y, z)));
+        for chunk in chunks.iter() {
+            for neighbour in chunks.iter() {
+                if shares_face(chunk, neighbour) {
+                    assert_seamless(chunk, neighbour);
                 }
             }
         }
+        chunks
     }
-}
-
 
+    #[test]
+    fn chunk_is_plain_data_test() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<Chunk>();
+    }
+}
@@ -0,0 +1,150 @@
+use bevy::math::IVec3;
+use crate::chunk_storage::{ChunkVoxels, PaletteStorage};
+use crate::voxel::Voxel;
+
+// The layer of voxels just outside each of a chunk's six faces, so the mesher can tell whether a border face is
+// hidden without generating or looking up the neighbouring chunk. Faces are ordered -x, +x, -y, +y, -z, +z and
+// each is stored like the chunk itself, so an all air or all stone face is a single voxel
+#[derive(Clone, Debug)]
+pub struct ChunkBorder {
+    size: IVec3,
+    faces: [ChunkVoxels; 6],
+}
+
+impl ChunkBorder {
+    pub fn filled(size: IVec3, voxel: Voxel) -> ChunkBorder {
+        ChunkBorder {
+            size,
+            faces: [0, 1, 2, 3, 4, 5].map(|face| ChunkVoxels::uniform(face_len(&size, face), voxel)),
+        }
+    }
+
+    // generate_voxel is given positions in the chunk's local space, i.e. with one coordinate at -1 or size
+    pub fn generate<F: Fn(&IVec3) -> Voxel>(size: IVec3, generate_voxel: F) -> ChunkBorder {
+        ChunkBorder {
+            size,
+            faces: [0, 1, 2, 3, 4, 5].map(|face| {
+                let len = face_len(&size, face);
+                let mut storage = PaletteStorage::new(len, generate_voxel(&face_position(&size, face, 0)));
+                for index in 1..len {
+                    storage.set(index, generate_voxel(&face_position(&size, face, index)));
+                }
+                ChunkVoxels::from_palette(storage)
+            }),
+        }
+    }
+
+    // True for positions one voxel outside a face, edges and corners are not stored
+    pub fn contains(&self, local_xyz: &IVec3) -> bool {
+        face_and_index(local_xyz, &self.size).is_some()
+    }
+
+    pub fn get(&self, local_xyz: &IVec3) -> Voxel {
+        let (face, index) = self.expect_face_and_index(local_xyz);
+        self.faces[face].get(index)
+    }
+
+    // Returns the voxel that was previously there
+    pub fn set(&mut self, local_xyz: &IVec3, voxel: Voxel) -> Voxel {
+        let (face, index) = self.expect_face_and_index(local_xyz);
+        self.faces[face].set(index, voxel)
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.faces.iter().map(|face| face.memory_usage()).sum()
+    }
+
+    fn expect_face_and_index(&self, local_xyz: &IVec3) -> (usize, usize) {
+        match face_and_index(local_xyz, &self.size) {
+            Some(face_and_index) => face_and_index,
+            None => panic!("{} {} {} is not on the border of a chunk of size {} {} {}",
+                           local_xyz.x, local_xyz.y, local_xyz.z, self.size.x, self.size.y, self.size.z)
+        }
+    }
+}
+
+// The two axes that lie along a face, for a face on the given axis
+fn face_axes(axis: usize) -> (usize, usize) {
+    match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1)
+    }
+}
+
+fn face_len(size: &IVec3, face: usize) -> usize {
+    let size = size.to_array();
+    let (u, v) = face_axes(face / 2);
+    (size[u] * size[v]) as usize
+}
+
+fn face_position(size: &IVec3, face: usize, index: usize) -> IVec3 {
+    let size = size.to_array();
+    let axis = face / 2;
+    let (u, v) = face_axes(axis);
+
+    let mut xyz = [0; 3];
+    xyz[axis] = if face % 2 == 1 { size[axis] } else { -1 };
+    xyz[u] = index as i32 % size[u];
+    xyz[v] = index as i32 / size[u];
+    IVec3::from(xyz)
+}
+
+fn face_and_index(local_xyz: &IVec3, size: &IVec3) -> Option<(usize, usize)> {
+    let xyz = local_xyz.to_array();
+    let size = size.to_array();
+
+    let mut face = None;
+    for axis in 0..3 {
+        if xyz[axis] == -1 || xyz[axis] == size[axis] {
+            if face.is_some() {
+                return None;
+            }
+            face = Some(axis * 2 + (xyz[axis] == size[axis]) as usize);
+        } else if xyz[axis] < -1 || xyz[axis] > size[axis] {
+            return None;
+        }
+    }
+
+    let face = face?;
+    let (u, v) = face_axes(face / 2);
+    Some((face, (xyz[u] + xyz[v] * size[u]) as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block;
+    use crate::chunk_border::ChunkBorder;
+    use crate::voxel::{BlockId, Voxel};
+    use bevy::math::IVec3;
+
+    #[test]
+    fn border_positions_test() {
+        let size = IVec3::new(4, 5, 6);
+        // Each face remembers which position it was generated for
+        let border = ChunkBorder::generate(size, |xyz| {
+            Voxel::new(BlockId((xyz.x + 1 + (xyz.y + 1) * 8 + (xyz.z + 1) * 64) as u16))
+        });
+
+        for (xyz, expected) in [
+            (IVec3::new(-1, 0, 0), true), (IVec3::new(4, 4, 5), true), (IVec3::new(2, -1, 3), true),
+            (IVec3::new(0, 5, 0), true), (IVec3::new(3, 2, -1), true), (IVec3::new(1, 1, 6), true),
+            (IVec3::new(1, 1, 1), false), (IVec3::new(-1, -1, 0), false), (IVec3::new(-2, 0, 0), false),
+        ] {
+            assert_eq!(border.contains(&xyz), expected);
+            if expected {
+                assert_eq!(border.get(&xyz).block_id.0 as i32, xyz.x + 1 + (xyz.y + 1) * 8 + (xyz.z + 1) * 64);
+            }
+        }
+    }
+
+    #[test]
+    fn border_set_test() {
+        let mut border = ChunkBorder::filled(IVec3::new(16, 16, 16), Voxel::AIR);
+        let stone = Voxel::new(block::STONE);
+        assert_eq!(border.set(&IVec3::new(16, 3, 7), stone), Voxel::AIR);
+        assert_eq!(border.get(&IVec3::new(16, 3, 7)), stone);
+        assert_eq!(border.get(&IVec3::new(16, 3, 8)), Voxel::AIR);
+        assert_eq!(border.get(&IVec3::new(-1, 3, 7)), Voxel::AIR);
+    }
+}
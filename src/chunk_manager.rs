@@ -1,37 +1,65 @@
 use std::ops::Range;
-use bevy::prelude::{Handle, Vec3};
+use std::sync::Arc;
+use bevy::prelude::{Handle, IVec3, Mesh, StandardMaterial, Vec3};
 use bevy::prelude::Entity;
-use crate::{IVec3, StandardMaterial};
+use crate::block::BlockRegistry;
+use crate::generation::pipeline::ChunkPipeline;
+use crate::generation::TerrainGenerator;
+use crate::world_settings::WorldSettings;
+
+// Chunks this far past the render distance stay in the pipeline, so moving back and forth doesn't regenerate them
+const FORGET_MARGIN: i32 = 4;
 
 pub struct SpawnedChunk {
     pub chunk_location: IVec3,
-    pub entity: Entity
+    // None when the chunk has nothing to draw
+    pub entity: Option<Entity>,
+    pub mesh: Option<Handle<Mesh>>,
+    // The water is a separate entity so it can be drawn see through
+    pub water_entity: Option<Entity>,
+    pub water_mesh: Option<Handle<Mesh>>,
+    // The remesh that produced the current mesh, 0 for the mesh built when the chunk was spawned
+    pub mesh_version: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct ChunkMaterials {
+    pub terrain: Handle<StandardMaterial>,
+    pub water: Handle<StandardMaterial>,
 }
 
-#[derive(bevy::prelude::Component)]
 pub struct ChunkManager {
     pub chunk_size: IVec3,
-    center_chunk_location: IVec3, // The chunk_spawner the player is in
-    atlas: Handle<StandardMaterial>,
-
+    pub terrain_generator: Arc<dyn TerrainGenerator>,
+    materials: ChunkMaterials,
+    pub block_registry: Arc<BlockRegistry>,
+    // Chunks on their way through the generation stages
+    pub pipeline: ChunkPipeline,
 
     chunks_currently_being_spawned: std::sync::Mutex<Vec<IVec3>>,
     spawned_chunks: std::sync::Mutex<Vec<SpawnedChunk>>,
 
     chunk_render_distance: i32,
+    // Chunk layers loaded below and above the center chunk
     chunk_render_distance_y_range: Range<i32>,
+
+    next_mesh_version: u64,
 }
 
 impl ChunkManager {
-    pub fn new(chunk_size: IVec3, center_chunk_location: IVec3, atlas: Handle<StandardMaterial>) -> ChunkManager {
+    pub fn new(world_settings: &WorldSettings, materials: ChunkMaterials,
+               block_registry: Arc<BlockRegistry>, terrain_generator: Arc<dyn TerrainGenerator>) -> ChunkManager {
         ChunkManager {
-            chunk_size,
-            center_chunk_location,
-            atlas,
+            chunk_size: world_settings.chunk_size,
+            terrain_generator,
+            materials,
+            block_registry,
+            pipeline: ChunkPipeline::new(world_settings.chunk_size),
             spawned_chunks: std::sync::Mutex::new(Vec::new()),
             chunks_currently_being_spawned: std::sync::Mutex::new(Vec::new()), // The thread is doing work to spawn this chunk_spawner, once spawned it is removed from here and pushed to spawned_chunks
-            chunk_render_distance: 8,
-            chunk_render_distance_y_range: 0..1,
+            chunk_render_distance: world_settings.render_distance,
+            chunk_render_distance_y_range: -world_settings.render_distance_down..world_settings.render_distance_up + 1,
+            next_mesh_version: 1,
         }
     }
 
@@ -60,7 +88,11 @@ impl ChunkManager {
     }
 
     pub fn clone_material(&self) -> Handle<StandardMaterial> {
-        self.atlas.clone()
+        self.materials.terrain.clone()
+    }
+
+    pub fn clone_water_material(&self) -> Handle<StandardMaterial> {
+        self.materials.water.clone()
     }
 
     pub fn chunks_to_despawn(&mut self, center_chunk: IVec3) -> Vec<IVec3> {
@@ -74,7 +106,7 @@ impl ChunkManager {
                 println!("Adding chunk_spawner {} {} {} to despawn list because not the same as center_chunk {} {} {}",
                          spawned_chunk.chunk_location.x, spawned_chunk.chunk_location.y, spawned_chunk.chunk_location.z,
                 center_chunk.x, center_chunk.y, center_chunk.z);
-                to_despawn.push(spawned_chunk.chunk_location.clone());
+                to_despawn.push(spawned_chunk.chunk_location);
             }
         }
 
@@ -88,20 +120,53 @@ impl ChunkManager {
         self.spawned_chunks.lock().unwrap().push(spawned_chunk);
     }
 
-    pub fn despawn_chunk(&mut self, chunk_location: IVec3) -> std::option::Option<Entity> {
+    // Returns the chunk's terrain and water entities, if it had any
+    pub fn despawn_chunk(&mut self, chunk_location: IVec3) -> Vec<Entity> {
+        self.pipeline.remove(&chunk_location);
         let mut spawned_chunks = self.spawned_chunks.lock().unwrap();
 
         // TODO if chunk_spawner is being spawned right now, find some way to cancel it, and remove from being_spawned vec
 
         for i in 0..spawned_chunks.len() {
             if spawned_chunks.get(i).unwrap().chunk_location == chunk_location {
-                let entity = spawned_chunks.get(i).unwrap().entity;
-                spawned_chunks.remove(i);
-                return Some(entity);
+                let spawned_chunk = spawned_chunks.remove(i);
+                return spawned_chunk.entity.into_iter().chain(spawned_chunk.water_entity).collect();
             }
         }
 
-        None
+        Vec::new()
+    }
+
+    // Runs f on the spawned chunk at chunk_location, returns false if there is no such chunk (e.g. it was despawned)
+    pub fn modify_spawned_chunk<F: FnOnce(&mut SpawnedChunk)>(&mut self, chunk_location: &IVec3, f: F) -> bool {
+        let mut spawned_chunks = self.spawned_chunks.lock().unwrap();
+        match spawned_chunks.iter_mut().find(|spawned_chunk| spawned_chunk.chunk_location == *chunk_location) {
+            Some(spawned_chunk) => {
+                f(spawned_chunk);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Remeshes can finish out of order, later versions win
+    pub fn next_mesh_version(&mut self) -> u64 {
+        let version = self.next_mesh_version;
+        self.next_mesh_version += 1;
+        version
+    }
+
+    // Drops pipeline chunks far enough outside the render zone that they won't be needed soon, e.g. the neighbours
+    // generated around chunks that have since been despawned
+    pub fn forget_chunks_outside(&mut self, center_chunk: IVec3) {
+        let reach = self.chunk_render_distance + FORGET_MARGIN;
+        let y_range = self.chunk_render_distance_y_range.start - FORGET_MARGIN..self.chunk_render_distance_y_range.end + FORGET_MARGIN;
+        let keep = |chunk_location: &IVec3| {
+            let offset = *chunk_location - center_chunk;
+            offset.x.abs() <= reach && offset.z.abs() <= reach && y_range.contains(&offset.y)
+        };
+        self.pipeline.forget(keep);
+        self.chunks_currently_being_spawned.lock().unwrap().retain(keep);
     }
 
     pub fn set_chunk_being_spawned(&mut self, chunk: IVec3) {
@@ -114,17 +179,17 @@ impl ChunkManager {
         let range = -self.chunk_render_distance..self.chunk_render_distance;
 
         for x in range.clone() {
-            for y in range.clone() {
-                //for z in self.chunk_render_distance_y_range.clone() {
-                    chunks_in_render_zone.push(IVec3::new(x, 0, y));
-                //}
+            for z in range.clone() {
+                for y in self.chunk_render_distance_y_range.clone() {
+                    chunks_in_render_zone.push(IVec3::new(x, y, z));
+                }
             }
         }
         chunks_in_render_zone.sort_by(|a: &IVec3, b: &IVec3| {
             let max_a = i32::max(i32::max(a.x.abs(), a.y.abs()), a.z.abs());
             let max_b = i32::max(i32::max(b.x.abs(), b.y.abs()), b.z.abs());
 
-            return i32::cmp(&max_a, &max_b);
+            i32::cmp(&max_a, &max_b)
         });
 
         let mut chunks_in_render_zone_worldspace = Vec::new();
@@ -143,7 +208,7 @@ impl ChunkManager {
 
         for chunk in chunks_in_render_zone {
             if !self.is_chunk_spawned_or_spawning(&chunk) {
-                chunks_to_spawn.push(chunk.clone());
+                chunks_to_spawn.push(chunk);
             }
         }
 
@@ -152,48 +217,20 @@ impl ChunkManager {
 }
 
 pub fn get_chunk_containing_position(position: &Vec3, chunk_size: &IVec3) -> IVec3 {
-    let mut chunk_offset_x = 0;
-    //let mut chunk_offset_y = 0;
-    let mut chunk_offset_z = 0;
-
-    if position.x < 0.0 {
-        chunk_offset_x = -1;
-    }
-
-    // if position.y < 0.0 {
-    //     chunk_offset_y = -1;
-    // }
-
-    if position.z < 0.0 {
-        chunk_offset_z = -1;
-    }
-
-    IVec3::new((position.x / chunk_size.x as f32) as i32 + chunk_offset_x,
-               0,//(position.y / CHUNK_SIZE_Y as f32) as i32 + chunk_offset_y,
-               (position.z / chunk_size.z as f32) as i32 + chunk_offset_z)
+    (*position / chunk_size.as_vec3()).floor().as_ivec3()
 }
 
-fn get_rings(layers: &Vec<i32>, inds: &Vec<(i32, i32)>) -> Vec<(i32, i32)> {
-    let mut ring_indexes = Vec::new();
-    for layer in layers {
-        ring_indexes.extend_from_slice(&get_ring(layer, inds));
+#[cfg(test)]
+mod tests {
+    use crate::chunk_manager::get_chunk_containing_position;
+    use bevy::math::{IVec3, Vec3};
+
+    #[test]
+    fn get_chunk_containing_position_test() {
+        let size = IVec3::new(32, 32, 32);
+        assert_eq!(get_chunk_containing_position(&Vec3::new(0.0, 0.0, 0.0), &size), IVec3::new(0, 0, 0));
+        assert_eq!(get_chunk_containing_position(&Vec3::new(31.9, 64.0, 32.0), &size), IVec3::new(0, 2, 1));
+        assert_eq!(get_chunk_containing_position(&Vec3::new(-0.5, -1.0, -32.0), &size), IVec3::new(-1, -1, -1));
+        assert_eq!(get_chunk_containing_position(&Vec3::new(-32.5, -64.0, -65.0), &size), IVec3::new(-2, -2, -3));
     }
-    ring_indexes
 }
-
-fn get_ring(layer: &i32, inds: &Vec<(i32, i32)>) -> Vec<(i32, i32)> {
-    // 0 -> 0
-    // layer = 1 -> 1,2,3,4,5,6,7,8 -> (1^2)..(3^2)
-    // layer = 2 -> 9 ... 24        -> (3^2)...(5^2 - 1)
-    // layer = 3 -> (5^2) -> (7^2 - 1)
-
-    if *layer == 0 {
-        return inds[0..1].to_vec();
-    } else {
-        let start = ((layer * 2) - 1) as usize;
-        let end = start + 2;
-
-        println!("Inds: {} {}", start*start, end*end);
-        return inds[(start*start)..(end*end)].to_vec();
-    }
-}
\ No newline at end of file
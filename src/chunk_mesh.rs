@@ -1,47 +1,36 @@
 use bevy::prelude::Mesh;
-use crate::chunk_vertexes::{generate_chunk_quad_groups, QuadDirection, VoxelQuads};
-use crate::{Chunk};
+use crate::block::BlockRegistry;
+use crate::chunk_vertexes::{generate_chunk_quad_groups, MeshLayer, VoxelQuads};
+use crate::chunk::Chunk;
 
-pub fn generate_mesh(chunk: &Chunk) -> Mesh {
-    let voxel_quad_groups = generate_chunk_quad_groups(&chunk);
-    create_chunk_mesh(&voxel_quad_groups)
+// None when the chunk has no visible faces, i.e. it is entirely air or entirely buried
+pub fn generate_mesh(chunk: &Chunk, block_registry: &BlockRegistry) -> Option<Mesh> {
+    generate_layer_mesh(chunk, block_registry, MeshLayer::Terrain)
 }
 
-fn create_chunk_mesh(quads: &VoxelQuads) -> Mesh {
-    // TODO group the vertices by quads (every 6 vertices = quad), determine which face they are,
-    // TODO pick a texture based on that (grass top dirt side)
+// The surfaces of the chunk's water, drawn with a see through material. None when no water meets air
+pub fn generate_water_mesh(chunk: &Chunk, block_registry: &BlockRegistry) -> Option<Mesh> {
+    generate_layer_mesh(chunk, block_registry, MeshLayer::Water)
+}
+
+fn generate_layer_mesh(chunk: &Chunk, block_registry: &BlockRegistry, layer: MeshLayer) -> Option<Mesh> {
+    let voxel_quad_groups = generate_chunk_quad_groups(chunk, block_registry, layer);
+    if voxel_quad_groups.is_empty() {
+        return None;
+    }
+
+    Some(create_chunk_mesh(&voxel_quad_groups, block_registry))
+}
+
+fn create_chunk_mesh(quads: &VoxelQuads, block_registry: &BlockRegistry) -> Mesh {
     let mut positions = Vec::new();
     let mut normals = Vec::new();
     let mut uvs = Vec::new();
 
     for voxel_quad in quads {
-
-        let texture_atlas_index : i32;
-        let height = voxel_quad.y as f32;
-
-        if height >= 24.0 {
-            // Top = grass, rest = dirt
-            texture_atlas_index = match voxel_quad.quad.direction {
-                QuadDirection::TOP => 1,
-                _ => 4
-            };
-        } else if height >= 18.0 {
-
-            // Top = grass, rest = dirt
-            texture_atlas_index = match voxel_quad.quad.direction {
-                QuadDirection::TOP => 0,
-                _ => 4
-            };
-
-        } else if height >= 10.0 {
-            // Top = grass, rest = dirt
-            texture_atlas_index = match voxel_quad.quad.direction {
-                QuadDirection::TOP => 2,
-                _ => 4
-            };
-        } else {
-            texture_atlas_index = 3;
-        }
+        let texture_atlas_index = block_registry.get(voxel_quad.block_id)
+            .textures
+            .for_direction(voxel_quad.quad.direction);
 
         for vertex in voxel_quad.quad.vertexes {
             let (position, normal, uv) = &vertex;
@@ -78,5 +67,5 @@ fn uvs_to_atlas_uvs(uvs: &[f32;2], atlas_width: i32, atlas_index: i32) -> [f32;
         new_uv[1] = (y_index + 1) as f32 * texture_width;
     }
 
-    return new_uv;
+    new_uv
 }
\ No newline at end of file
@@ -1,13 +1,12 @@
 use bevy::app::App;
-use bevy::math::IVec3;
-use bevy::prelude::{Assets, Commands, Entity, Mesh, PbrBundle, Plugin, Query, Res, ResMut, Transform, With};
-use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::prelude::{Commands, Entity, Plugin, Query, ResMut};
+use bevy::tasks::Task;
 use futures_lite::future;
-use crate::{Chunk, ChunkManager, FlyCamera, generate_mesh};
-use crate::chunk_manager::{get_chunk_containing_position, SpawnedChunk};
+use crate::voxel_world::VoxelWorld;
 use crate::chunk_spawner::spawn_chunks::spawn_chunks;
 use crate::chunk_spawner::render_voxel_mesh::render_voxel_mesh;
-use crate::chunk_spawner::tasks::{DespawnChunkTask, RenderChunkMeshesTask};
+use crate::chunk_spawner::remesh_chunks::{apply_chunk_remeshes, queue_chunk_remeshes};
+use crate::chunk_spawner::tasks::DespawnChunkTask;
 
 pub struct ChunkSpawnerPlugin;
 
@@ -16,6 +15,8 @@ impl Plugin for ChunkSpawnerPlugin {
         app
             .add_system(spawn_chunks)
             .add_system(render_voxel_mesh)
+            .add_system(queue_chunk_remeshes)
+            .add_system(apply_chunk_remeshes)
             .add_system(despawn_chunk_processor);
     }
 }
@@ -23,16 +24,16 @@ impl Plugin for ChunkSpawnerPlugin {
 fn despawn_chunk_processor(
     mut commands: Commands,
     mut despawn_chunk_tasks: Query<(Entity, &mut Task<DespawnChunkTask>)>,
-    mut chunk_manager_query: Query<&mut ChunkManager>
+    mut voxel_world: ResMut<VoxelWorld>
 ) {
-    let mut chunk_manager = chunk_manager_query.single_mut();
     for (entity, mut task) in despawn_chunk_tasks.iter_mut() {
         if let Some(despawn_chunk_task) = future::block_on(future::poll_once(&mut *task)) {
             println!("Despawning chunk_spawner {} {} {}", despawn_chunk_task.chunk.x, despawn_chunk_task.chunk.y, despawn_chunk_task.chunk.z);
-            let entity_to_despawn = chunk_manager.despawn_chunk(despawn_chunk_task.chunk);
+            let entities_to_despawn = voxel_world.chunk_manager.despawn_chunk(despawn_chunk_task.chunk);
+            voxel_world.remove_chunk(&despawn_chunk_task.chunk);
 
-            if entity_to_despawn.is_some() {
-                commands.entity(entity_to_despawn.unwrap()).despawn();
+            for entity_to_despawn in entities_to_despawn {
+                commands.entity(entity_to_despawn).despawn();
             }
             commands.entity(entity).remove::<Task<DespawnChunkTask>>();
         }
@@ -0,0 +1,101 @@
+use bevy::prelude::{Assets, Commands, Entity, Handle, Mesh, PbrBundle, Query, Res, ResMut, StandardMaterial, Transform};
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use crate::chunk_spawner::tasks::RemeshChunkTask;
+use crate::chunk_mesh::generate_mesh;
+use crate::voxel_world::VoxelWorld;
+use crate::chunk_mesh::generate_water_mesh;
+
+// Rebuilds the mesh of every chunk edited since the last frame, however many edits it had
+pub fn queue_chunk_remeshes(
+    mut commands: Commands,
+    mut voxel_world: ResMut<VoxelWorld>,
+    thread_pool: Res<AsyncComputeTaskPool>,
+) {
+    for chunk_location in voxel_world.take_dirty_chunks() {
+        let chunk = voxel_world.get_chunk(&chunk_location).unwrap().clone();
+        let block_registry = voxel_world.chunk_manager.block_registry.clone();
+        let mesh_version = voxel_world.chunk_manager.next_mesh_version();
+
+        let task = thread_pool.spawn(async move {
+            RemeshChunkTask {
+                chunk_location,
+                mesh: generate_mesh(&chunk, &block_registry),
+                water_mesh: generate_water_mesh(&chunk, &block_registry),
+                mesh_version,
+            }
+        });
+
+        commands.spawn().insert(task);
+    }
+}
+
+// Swaps the new mesh into the chunk's existing mesh asset, only spawning or despawning an entity when the chunk
+// gains its first visible face or loses its last one
+pub fn apply_chunk_remeshes(
+    mut commands: Commands,
+    mut remesh_tasks: Query<(Entity, &mut Task<RemeshChunkTask>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut voxel_world: ResMut<VoxelWorld>,
+) {
+    for (entity, mut task) in remesh_tasks.iter_mut() {
+        if let Some(remesh_task) = future::block_on(future::poll_once(&mut *task)) {
+            commands.entity(entity).remove::<Task<RemeshChunkTask>>();
+
+            let transform = match voxel_world.get_chunk(&remesh_task.chunk_location) {
+                Some(chunk) => chunk.get_transform(),
+                // Despawned while the mesh was being built
+                None => continue
+            };
+            let material = voxel_world.chunk_manager.clone_material();
+            let water_material = voxel_world.chunk_manager.clone_water_material();
+
+            voxel_world.chunk_manager.modify_spawned_chunk(&remesh_task.chunk_location, |spawned_chunk| {
+                if remesh_task.mesh_version < spawned_chunk.mesh_version {
+                    return;
+                }
+                spawned_chunk.mesh_version = remesh_task.mesh_version;
+
+                swap_mesh(&mut commands, &mut meshes, remesh_task.mesh, &mut spawned_chunk.entity,
+                          &mut spawned_chunk.mesh, material, transform);
+                swap_mesh(&mut commands, &mut meshes, remesh_task.water_mesh, &mut spawned_chunk.water_entity,
+                          &mut spawned_chunk.water_mesh, water_material, transform);
+            });
+        }
+    }
+}
+
+// Updates one of the chunk's meshes, the terrain or the water
+fn swap_mesh(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    new_mesh: Option<Mesh>,
+    entity: &mut Option<Entity>,
+    mesh_handle: &mut Option<Handle<Mesh>>,
+    material: Handle<StandardMaterial>,
+    transform: Transform,
+) {
+    match (new_mesh, mesh_handle.as_ref()) {
+        (Some(mesh), Some(existing_handle)) => {
+            if let Some(existing_mesh) = meshes.get_mut(existing_handle) {
+                *existing_mesh = mesh;
+            }
+        },
+        (Some(mesh), None) => {
+            let new_handle = meshes.add(mesh);
+            *entity = Some(commands.spawn_bundle(PbrBundle {
+                mesh: new_handle.clone(),
+                material,
+                transform,
+                ..Default::default()
+            }).id());
+            *mesh_handle = Some(new_handle);
+        },
+        (None, _) => {
+            if let Some(chunk_entity) = entity.take() {
+                commands.entity(chunk_entity).despawn();
+            }
+            *mesh_handle = None;
+        }
+    }
+}
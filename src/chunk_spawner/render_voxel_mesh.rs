@@ -2,44 +2,51 @@ use bevy::prelude::{Assets, Commands, Entity, Mesh, PbrBundle, Query, ResMut};
 use bevy::tasks::Task;
 use futures_lite::future;
 use crate::chunk_manager::SpawnedChunk;
-use crate::chunk_spawner::tasks::RenderChunkMeshesTask;
-use crate::ChunkManager;
+use crate::generation::pipeline::FinishedStage;
+use crate::voxel_world::VoxelWorld;
 
+// Hands finished stages back to the pipeline, and spawns the chunks that have been meshed
 pub fn render_voxel_mesh(
     mut commands: Commands,
-    mut transform_tasks: Query<(Entity, &mut Task<RenderChunkMeshesTask>)>,
+    mut stage_tasks: Query<(Entity, &mut Task<FinishedStage>)>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut chunk_manager_query: Query<&mut ChunkManager>) {
+    mut voxel_world: ResMut<VoxelWorld>) {
 
-    let mut chunk_manager = chunk_manager_query.single_mut();
-
-    for (entity, mut task) in transform_tasks.iter_mut() {
-        if let Some(render_chunk_mesh_task) = future::block_on(future::poll_once(&mut *task)) {
-            let chunk = render_chunk_mesh_task.chunk;
-            let voxel_mesh = render_chunk_mesh_task.mesh;
-
-            println!("Spawning chunk_spawner {} {} {}", chunk.location.x, chunk.location.y, chunk.location.z);
+    for (entity, mut task) in stage_tasks.iter_mut() {
+        if let Some(finished_stage) = future::block_on(future::poll_once(&mut *task)) {
+            voxel_world.chunk_manager.pipeline.finish(finished_stage);
+            commands.entity(entity).remove::<Task<FinishedStage>>();
+        }
+    }
 
-            let mut pbr_bundles = Vec::new();
+    for (chunk, voxel_mesh, water_mesh) in voxel_world.chunk_manager.pipeline.take_meshed() {
+        println!("Spawning chunk_spawner {} {} {}", chunk.location.x, chunk.location.y, chunk.location.z);
 
-            let chunk_transform = chunk.get_transform();
+        let chunk_transform = chunk.get_transform();
 
-            pbr_bundles.push(PbrBundle {
-                mesh: meshes.add(voxel_mesh).clone(),
-                material: chunk_manager.clone_material(),
-                transform: chunk_transform,
-                ..Default::default()
+        let mut spawn_mesh = |mesh: Option<Mesh>, material| {
+            let mesh_handle = mesh.map(|mesh| meshes.add(mesh));
+            let entity = mesh_handle.as_ref().map(|mesh_handle| {
+                commands.spawn_bundle(PbrBundle {
+                    mesh: mesh_handle.clone(),
+                    material,
+                    transform: chunk_transform,
+                    ..Default::default()
+                }).id()
             });
-
-            for pbr_bundle in pbr_bundles.into_iter() {
-                let entity = commands.spawn_bundle(pbr_bundle).id();
-                chunk_manager.add_chunk_entity(SpawnedChunk {
-                    chunk_location: chunk.location,
-                    entity,
-                });
-            }
-
-            commands.entity(entity).remove::<Task<RenderChunkMeshesTask>>();
-        }
+            (entity, mesh_handle)
+        };
+        let (chunk_entity, mesh_handle) = spawn_mesh(voxel_mesh, voxel_world.chunk_manager.clone_material());
+        let (water_entity, water_mesh_handle) = spawn_mesh(water_mesh, voxel_world.chunk_manager.clone_water_material());
+
+        voxel_world.chunk_manager.add_chunk_entity(SpawnedChunk {
+            chunk_location: chunk.location,
+            entity: chunk_entity,
+            mesh: mesh_handle,
+            water_entity,
+            water_mesh: water_mesh_handle,
+            mesh_version: 0,
+        });
+        voxel_world.insert_chunk(chunk);
     }
-}
\ No newline at end of file
+}
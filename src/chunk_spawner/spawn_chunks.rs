@@ -1,16 +1,19 @@
-use bevy::prelude::{Commands, IVec3, Query, Res, Transform, With};
+use bevy::prelude::{Commands, IVec3, Query, Res, ResMut, Transform, With};
 use bevy::tasks::AsyncComputeTaskPool;
-use crate::{Chunk, ChunkManager, FlyCamera, generate_mesh, get_chunk_containing_position};
-use crate::chunk_spawner::tasks::{DespawnChunkTask, RenderChunkMeshesTask};
+use crate::flycamerafork::fly_camera::FlyCamera;
+use crate::chunk_manager::get_chunk_containing_position;
+use crate::voxel_world::VoxelWorld;
+use crate::chunk_spawner::tasks::DespawnChunkTask;
+use crate::generation::pipeline::ChunkStatus;
 
 pub fn spawn_chunks(
     mut commands: Commands,
     camera_query: Query<&Transform, With<FlyCamera>>,
-    mut chunk_manager_query: Query<&mut ChunkManager>,
+    mut voxel_world: ResMut<VoxelWorld>,
     thread_pool: Res<AsyncComputeTaskPool>,
 ) {
     let camera_transform = camera_query.single();
-    let mut chunk_manager = chunk_manager_query.single_mut();
+    let chunk_manager = &mut voxel_world.chunk_manager;
 
     let player_chunk = get_chunk_containing_position(&camera_transform.translation, &chunk_manager.chunk_size);
     println!("Player is in Chunk region {} {} {} with position {} {} {}",
@@ -25,24 +28,25 @@ pub fn spawn_chunks(
         });
         commands.spawn().insert(task);
     }
+    chunk_manager.forget_chunks_outside(player_chunk);
 
     let chunks_to_spawn = chunk_manager.request_chunks_to_spawn(player_chunk);
 
     for chunk_to_spawn in chunks_to_spawn {
-        chunk_manager.set_chunk_being_spawned(chunk_to_spawn.clone());
+        chunk_manager.set_chunk_being_spawned(chunk_to_spawn);
+        chunk_manager.pipeline.request(chunk_to_spawn, ChunkStatus::Meshed);
+    }
 
-        let chunk_size = chunk_manager.chunk_size.clone();
+    // Every stage that can run now, the neighbours' stages included, each on its own task
+    for job in chunk_manager.pipeline.ready_jobs(player_chunk) {
+        let chunk_size = chunk_manager.chunk_size;
+        let terrain_generator = chunk_manager.terrain_generator.clone();
+        let block_registry = chunk_manager.block_registry.clone();
 
         let task = thread_pool.spawn(async move {
-            let chunk = Chunk::noise(chunk_size, chunk_to_spawn.clone());
-            let mesh = generate_mesh(&chunk);
-
-            RenderChunkMeshesTask {
-                chunk,
-                mesh,
-            }
+            job.run(terrain_generator.as_ref(), &block_registry, chunk_size)
         });
 
         commands.spawn().insert(task);
     }
-}
\ No newline at end of file
+}
@@ -1,9 +1,10 @@
 use bevy::prelude::{IVec3, Mesh};
-use crate::Chunk;
 
-pub struct RenderChunkMeshesTask {
-    pub mesh: Mesh,
-    pub chunk: Chunk,
+pub struct RemeshChunkTask {
+    pub chunk_location: IVec3,
+    pub mesh: Option<Mesh>,
+    pub water_mesh: Option<Mesh>,
+    pub mesh_version: u64,
 }
 
 pub struct DespawnChunkTask {
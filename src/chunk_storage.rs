@@ -0,0 +1,320 @@
+use std::mem::size_of;
+use crate::voxel::Voxel;
+
+// A chunk's voxels. Chunks that are entirely one voxel (all air above the terrain, all stone below it) are kept
+// as a single value and only promoted to a palette on the first edit
+#[derive(Clone, Debug)]
+pub enum ChunkVoxels {
+    Uniform { voxel: Voxel, len: usize },
+    Palette(PaletteStorage),
+}
+
+impl ChunkVoxels {
+    pub fn uniform(len: usize, voxel: Voxel) -> ChunkVoxels {
+        ChunkVoxels::Uniform { voxel, len }
+    }
+
+    // Demotes to uniform if the palette only ever saw one voxel
+    pub fn from_palette(storage: PaletteStorage) -> ChunkVoxels {
+        if storage.palette().len() == 1 {
+            ChunkVoxels::uniform(storage.len(), storage.palette()[0])
+        } else {
+            ChunkVoxels::Palette(storage)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ChunkVoxels::Uniform { len, .. } => *len,
+            ChunkVoxels::Palette(storage) => storage.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn uniform_voxel(&self) -> Option<Voxel> {
+        match self {
+            ChunkVoxels::Uniform { voxel, .. } => Some(*voxel),
+            ChunkVoxels::Palette(_) => None,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Voxel {
+        match self {
+            ChunkVoxels::Uniform { voxel, .. } => *voxel,
+            ChunkVoxels::Palette(storage) => storage.get(index),
+        }
+    }
+
+    // Returns the voxel that was previously at index
+    pub fn set(&mut self, index: usize, voxel: Voxel) -> Voxel {
+        if let ChunkVoxels::Uniform { voxel: uniform_voxel, len } = *self {
+            if uniform_voxel == voxel {
+                return voxel;
+            }
+            *self = ChunkVoxels::Palette(PaletteStorage::new(len, uniform_voxel));
+        }
+
+        match self {
+            ChunkVoxels::Palette(storage) => storage.set(index, voxel),
+            ChunkVoxels::Uniform { .. } => unreachable!(),
+        }
+    }
+
+    pub fn compact(&mut self) {
+        if let ChunkVoxels::Palette(storage) = self {
+            storage.compact();
+            *self = ChunkVoxels::from_palette(storage.clone());
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            ChunkVoxels::Uniform { .. } => size_of::<ChunkVoxels>(),
+            ChunkVoxels::Palette(storage) => size_of::<ChunkVoxels>() - size_of::<PaletteStorage>() + storage.memory_usage(),
+        }
+    }
+}
+
+// Stores voxels as indexes into a per-chunk palette of distinct voxels. Indexes are bit-packed into u64 words
+// and never straddle two words, the number of bits per index grows as the palette grows
+#[derive(Clone, Debug)]
+pub struct PaletteStorage {
+    palette: Vec<Voxel>,
+    bits_per_index: u32,
+    data: Vec<u64>,
+    len: usize,
+}
+
+impl PaletteStorage {
+    pub fn new(len: usize, initial: Voxel) -> PaletteStorage {
+        let bits_per_index = 1;
+        PaletteStorage {
+            palette: vec![initial],
+            bits_per_index,
+            data: vec![0; words_needed(len, bits_per_index)],
+            len,
+        }
+    }
+
+    pub fn from_voxels(voxels: &[Voxel]) -> PaletteStorage {
+        let mut storage = PaletteStorage::new(voxels.len(), voxels.first().copied().unwrap_or(Voxel::AIR));
+        for (index, voxel) in voxels.iter().enumerate() {
+            storage.set(index, *voxel);
+        }
+        storage
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn palette(&self) -> &[Voxel] {
+        &self.palette
+    }
+
+    pub fn bits_per_index(&self) -> u32 {
+        self.bits_per_index
+    }
+
+    pub fn get(&self, index: usize) -> Voxel {
+        self.palette[self.get_palette_index(index)]
+    }
+
+    // Returns the voxel that was previously at index
+    pub fn set(&mut self, index: usize, voxel: Voxel) -> Voxel {
+        let palette_index = match self.palette.iter().position(|v| *v == voxel) {
+            Some(palette_index) => palette_index,
+            None => {
+                if self.palette.len() == 1 << self.bits_per_index {
+                    self.repack(self.bits_per_index + 1);
+                }
+                self.palette.push(voxel);
+                self.palette.len() - 1
+            }
+        };
+
+        let previous = self.get(index);
+        self.set_palette_index(index, palette_index);
+        previous
+    }
+
+    // Removes palette entries that are no longer referenced and shrinks the index size to fit
+    pub fn compact(&mut self) {
+        let mut used = vec![false; self.palette.len()];
+        for index in 0..self.len {
+            used[self.get_palette_index(index)] = true;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        for (old_index, voxel) in self.palette.iter().enumerate() {
+            if used[old_index] {
+                remap[old_index] = palette.len();
+                palette.push(*voxel);
+            }
+        }
+
+        if palette.is_empty() {
+            palette.push(self.palette[0]);
+        }
+
+        let mut compacted = PaletteStorage {
+            bits_per_index: bits_for_palette_len(palette.len()),
+            data: Vec::new(),
+            palette,
+            len: self.len,
+        };
+        compacted.data = vec![0; words_needed(self.len, compacted.bits_per_index)];
+
+        for index in 0..self.len {
+            compacted.set_palette_index(index, remap[self.get_palette_index(index)]);
+        }
+
+        *self = compacted;
+    }
+
+    // Approximate heap + inline size in bytes
+    pub fn memory_usage(&self) -> usize {
+        size_of::<PaletteStorage>()
+            + self.palette.capacity() * size_of::<Voxel>()
+            + self.data.capacity() * size_of::<u64>()
+    }
+
+    fn indexes_per_word(&self) -> usize {
+        (64 / self.bits_per_index) as usize
+    }
+
+    fn get_palette_index(&self, index: usize) -> usize {
+        assert!(index < self.len, "Voxel index {} out of bounds for storage of {}", index, self.len);
+        let indexes_per_word = self.indexes_per_word();
+        let word = self.data[index / indexes_per_word];
+        let shift = (index % indexes_per_word) as u32 * self.bits_per_index;
+        ((word >> shift) & mask(self.bits_per_index)) as usize
+    }
+
+    fn set_palette_index(&mut self, index: usize, palette_index: usize) {
+        assert!(index < self.len, "Voxel index {} out of bounds for storage of {}", index, self.len);
+        let indexes_per_word = self.indexes_per_word();
+        let shift = (index % indexes_per_word) as u32 * self.bits_per_index;
+        let word = &mut self.data[index / indexes_per_word];
+        *word &= !(mask(self.bits_per_index) << shift);
+        *word |= (palette_index as u64) << shift;
+    }
+
+    fn repack(&mut self, bits_per_index: u32) {
+        let mut repacked = PaletteStorage {
+            palette: Vec::new(),
+            bits_per_index,
+            data: vec![0; words_needed(self.len, bits_per_index)],
+            len: self.len,
+        };
+
+        for index in 0..self.len {
+            repacked.set_palette_index(index, self.get_palette_index(index));
+        }
+
+        self.bits_per_index = repacked.bits_per_index;
+        self.data = repacked.data;
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+fn words_needed(len: usize, bits_per_index: u32) -> usize {
+    let indexes_per_word = (64 / bits_per_index) as usize;
+    len.div_ceil(indexes_per_word)
+}
+
+fn bits_for_palette_len(palette_len: usize) -> u32 {
+    let mut bits = 1;
+    while (1 << bits) < palette_len {
+        bits += 1;
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk_storage::{ChunkVoxels, PaletteStorage};
+    use crate::voxel::{BlockId, Voxel};
+
+    #[test]
+    fn uniform_chunk_voxels_promote_and_demote_test() {
+        let stone = Voxel::new(BlockId(1));
+        let mut voxels = ChunkVoxels::uniform(4096, stone);
+        assert_eq!(voxels.get(123), stone);
+
+        // Writing the same voxel keeps it uniform
+        voxels.set(5, stone);
+        assert_eq!(voxels.uniform_voxel(), Some(stone));
+
+        assert_eq!(voxels.set(5, Voxel::AIR), stone);
+        assert_eq!(voxels.uniform_voxel(), None);
+        assert_eq!(voxels.get(5), Voxel::AIR);
+        assert_eq!(voxels.get(6), stone);
+
+        voxels.set(5, stone);
+        voxels.compact();
+        assert_eq!(voxels.uniform_voxel(), Some(stone));
+    }
+
+    #[test]
+    fn palette_storage_set_get_test() {
+        let mut storage = PaletteStorage::new(32 * 32 * 32, Voxel::AIR);
+        assert_eq!(storage.get(0), Voxel::AIR);
+        assert_eq!(storage.bits_per_index(), 1);
+
+        for i in 0..20u16 {
+            storage.set(i as usize * 7, Voxel::new(BlockId(i + 1)));
+        }
+
+        // 21 distinct voxels need 5 bits
+        assert_eq!(storage.palette().len(), 21);
+        assert_eq!(storage.bits_per_index(), 5);
+
+        for i in 0..20u16 {
+            assert_eq!(storage.get(i as usize * 7), Voxel::new(BlockId(i + 1)));
+        }
+        assert_eq!(storage.get(1), Voxel::AIR);
+        assert_eq!(storage.get(32 * 32 * 32 - 1), Voxel::AIR);
+    }
+
+    #[test]
+    fn palette_storage_compact_test() {
+        let mut storage = PaletteStorage::new(4096, Voxel::AIR);
+        for i in 0..10u16 {
+            storage.set(i as usize, Voxel::new(BlockId(i + 1)));
+        }
+        let memory_before = storage.memory_usage();
+
+        for i in 0..10 {
+            storage.set(i, Voxel::AIR);
+        }
+        storage.set(100, Voxel::new(BlockId(3)));
+        storage.compact();
+
+        assert_eq!(storage.palette(), &[Voxel::AIR, Voxel::new(BlockId(3))]);
+        assert_eq!(storage.bits_per_index(), 1);
+        assert!(storage.memory_usage() < memory_before);
+        assert_eq!(storage.get(100), Voxel::new(BlockId(3)));
+        assert_eq!(storage.get(5), Voxel::AIR);
+    }
+
+    #[test]
+    fn palette_storage_from_voxels_test() {
+        let voxels: Vec<Voxel> = (0..1000).map(|i| Voxel::new(BlockId((i % 3) as u16))).collect();
+        let storage = PaletteStorage::from_voxels(&voxels);
+        for (i, voxel) in voxels.iter().enumerate() {
+            assert_eq!(storage.get(i), *voxel);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::IVec3;
+use bevy::math::IVec3;
 
 pub fn xyz_to_voxel_index(xyz: &IVec3, chunk_size: &IVec3) -> usize {
     (xyz.x + (xyz.y * chunk_size.x) + (xyz.z * chunk_size.x * chunk_size.y)) as usize
@@ -9,13 +9,33 @@ pub fn voxel_index_to_xyz(idx: i32, chunk_size: &IVec3) -> IVec3 {
     let idx_yx = idx - (z * chunk_size.x * chunk_size.y);
     let y = idx_yx / chunk_size.x;
     let x = idx_yx % chunk_size.x;
-    return IVec3::new(x, y, z)
+    IVec3::new(x, y, z)
+}
+
+// Rounds towards negative infinity, so world x = -1 is in chunk -1 rather than chunk 0
+pub fn world_to_chunk_location(world_xyz: &IVec3, chunk_size: &IVec3) -> IVec3 {
+    IVec3::new(
+        world_xyz.x.div_euclid(chunk_size.x),
+        world_xyz.y.div_euclid(chunk_size.y),
+        world_xyz.z.div_euclid(chunk_size.z))
+}
+
+// The position of a world voxel inside the chunk that contains it, always 0..chunk_size
+pub fn world_to_local_xyz(world_xyz: &IVec3, chunk_size: &IVec3) -> IVec3 {
+    IVec3::new(
+        world_xyz.x.rem_euclid(chunk_size.x),
+        world_xyz.y.rem_euclid(chunk_size.y),
+        world_xyz.z.rem_euclid(chunk_size.z))
+}
+
+pub fn local_to_world_xyz(chunk_location: &IVec3, local_xyz: &IVec3, chunk_size: &IVec3) -> IVec3 {
+    *chunk_location * *chunk_size + *local_xyz
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::chunk_utils::voxel_index_to_xyz;
-    use crate::IVec3;
+    use crate::chunk_utils::{local_to_world_xyz, voxel_index_to_xyz, world_to_chunk_location, world_to_local_xyz};
+    use bevy::math::IVec3;
 
     #[test]
     fn voxel_index_to_xyz_test() {
@@ -29,4 +49,21 @@ mod tests {
         assert_eq!(voxel_index_to_xyz(32 * 32, &size), IVec3::new(0, 0, 1));
         assert_eq!(voxel_index_to_xyz(32 * 32 + 35, &size), IVec3::new(3, 1, 1));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn world_to_chunk_and_local_test() {
+        let size = IVec3::new(32, 32, 32);
+        let cases = [
+            (IVec3::new(0, 0, 0), IVec3::new(0, 0, 0), IVec3::new(0, 0, 0)),
+            (IVec3::new(31, 32, 33), IVec3::new(0, 1, 1), IVec3::new(31, 0, 1)),
+            (IVec3::new(-1, -32, -33), IVec3::new(-1, -1, -2), IVec3::new(31, 0, 31)),
+            (IVec3::new(-64, 65, -65), IVec3::new(-2, 2, -3), IVec3::new(0, 1, 31)),
+        ];
+
+        for (world, chunk, local) in cases {
+            assert_eq!(world_to_chunk_location(&world, &size), chunk);
+            assert_eq!(world_to_local_xyz(&world, &size), local);
+            assert_eq!(local_to_world_xyz(&chunk, &local, &size), world);
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use crate::{Chunk, IVec3};
+use crate::chunk::Chunk;
+use bevy::math::IVec3;
+use crate::block::BlockRegistry;
 use crate::chunk_utils::{voxel_index_to_xyz};
+use crate::voxel::{BlockId, Voxel};
 
-#[derive(PartialEq, Eq, Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum QuadDirection {
     TOP,
     BOTTOM,
@@ -21,6 +25,7 @@ pub struct Quad {
 
 pub struct VoxelQuad {
     pub quad: Quad,
+    pub block_id: BlockId,
     pub x: i32,
     pub y: i32,
     pub z: i32,
@@ -64,14 +69,37 @@ impl Quad {
 pub type Quads = Vec<Quad>;
 pub type VoxelQuads = Vec<VoxelQuad>;
 
+// Each chunk is drawn as two meshes, solid terrain and the see through liquids on top of it
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum MeshLayer {
+    Terrain,
+    Water,
+}
+
+impl MeshLayer {
+    fn draws(&self, voxel: &Voxel, block_registry: &BlockRegistry) -> bool {
+        match self {
+            MeshLayer::Terrain => block_registry.is_solid(voxel),
+            MeshLayer::Water => block_registry.is_liquid(voxel),
+        }
+    }
+
+    // Terrain shows through water, but water only has a surface where it meets air or other see through blocks
+    fn is_hidden_by(&self, neighbour: &Voxel, block_registry: &BlockRegistry) -> bool {
+        match self {
+            MeshLayer::Terrain => block_registry.is_opaque(neighbour),
+            MeshLayer::Water => block_registry.is_opaque(neighbour) || block_registry.is_liquid(neighbour),
+        }
+    }
+}
+
 #[exec_time]
-pub fn generate_chunk_quad_groups(chunk: &Chunk) -> VoxelQuads {
-    generate_chunk_mesh_from_voxel(chunk).voxel_quads
+pub fn generate_chunk_quad_groups(chunk: &Chunk, block_registry: &BlockRegistry, layer: MeshLayer) -> VoxelQuads {
+    generate_chunk_mesh_from_voxel(chunk, block_registry, layer).voxel_quads
 }
 
 struct ChunkMeshGenResult {
     pub voxel_quads: VoxelQuads,
-    pub visited: Vec<usize>
 }
 
 struct OffsetAndDirection {
@@ -79,50 +107,22 @@ struct OffsetAndDirection {
     pub direction: QuadDirection,
 }
 
-fn generate_chunk_mesh_from_voxel(chunk: &Chunk) -> ChunkMeshGenResult {
+fn generate_chunk_mesh_from_voxel(chunk: &Chunk, block_registry: &BlockRegistry, layer: MeshLayer) -> ChunkMeshGenResult {
     let mut voxel_quads: Vec<VoxelQuad> = Vec::new();
 
-    for n in 0..((chunk.size.x * chunk.size.y * chunk.size.z) as usize) {
-        if !chunk.voxels[n].solid {
-            continue
-        }
-
-        let center_voxel = voxel_index_to_xyz(n as usize as i32, &chunk.size);
-        let x = center_voxel.x;
-        let y = center_voxel.y;
-        let z = center_voxel.z;
-
-        let offset_and_directions : [OffsetAndDirection; 6] = [
-            OffsetAndDirection { offset: IVec3::new(-1, 0, 0), direction: QuadDirection::FRONT } ,
-            OffsetAndDirection { offset: IVec3::new(1, 0, 0), direction: QuadDirection::BACK } ,
-            OffsetAndDirection { offset: IVec3::new(0, 0, -1), direction: QuadDirection::LEFT } ,
-            OffsetAndDirection { offset: IVec3::new(0, 0, 1), direction: QuadDirection::RIGHT } ,
-            OffsetAndDirection { offset: IVec3::new(0, -1,  0),direction:  QuadDirection::BOTTOM } ,
-            OffsetAndDirection { offset: IVec3::new(0, 1,0), direction: QuadDirection::TOP } ,
-        ];
-
-        for offset_and_direction in offset_and_directions {
-            let neighbour_voxel_location = center_voxel + offset_and_direction.offset;
-            let neighbour_voxel = chunk.generate_voxel_in_localspace(&neighbour_voxel_location);
-
-            let mut add_face = || voxel_quads.push(generate_voxel_quad(offset_and_direction.direction.clone(), x, y, z));
-
-            // Height OOB - since no chunks above or below
-            if neighbour_voxel_location.y == chunk.size.y {
-                if offset_and_direction.direction == QuadDirection::TOP {
-                    add_face();
-                }
-                continue;
-            } else if neighbour_voxel_location.y == -1 {
-                if offset_and_direction.direction == QuadDirection::BOTTOM {
-                    add_face();
-                }
-                continue;
+    match chunk.voxels.uniform_voxel() {
+        // Uniform air (or any other block this layer doesn't draw), nothing to draw
+        Some(voxel) if !layer.draws(&voxel, block_registry) => {},
+        // Uniform solid or water, every interior face is hidden so only the shell needs checking
+        Some(_) => {
+            for xyz in chunk_shell(&chunk.size) {
+                add_voxel_faces(chunk, block_registry, layer, &xyz, &mut voxel_quads);
             }
-
-            if !neighbour_voxel.solid {
-                add_face();
-                continue;
+        },
+        None => {
+            for n in 0..((chunk.size.x * chunk.size.y * chunk.size.z) as usize) {
+                let xyz = voxel_index_to_xyz(n as i32, &chunk.size);
+                add_voxel_faces(chunk, block_registry, layer, &xyz, &mut voxel_quads);
             }
         }
     }
@@ -131,14 +131,64 @@ fn generate_chunk_mesh_from_voxel(chunk: &Chunk) -> ChunkMeshGenResult {
 
     ChunkMeshGenResult {
         voxel_quads,
-        visited: Vec::new(),
     }
 }
 
-fn generate_voxel_quad(quad_direction: QuadDirection, x: i32, y: i32, z: i32) -> VoxelQuad {
+fn add_voxel_faces(chunk: &Chunk, block_registry: &BlockRegistry, layer: MeshLayer, center_voxel: &IVec3,
+                   voxel_quads: &mut VoxelQuads) {
+    let voxel = chunk.get_voxel(center_voxel);
+    if !layer.draws(&voxel, block_registry) {
+        return
+    }
+
+    let x = center_voxel.x;
+    let y = center_voxel.y;
+    let z = center_voxel.z;
+
+    let offset_and_directions : [OffsetAndDirection; 6] = [
+        OffsetAndDirection { offset: IVec3::new(-1, 0, 0), direction: QuadDirection::FRONT } ,
+        OffsetAndDirection { offset: IVec3::new(1, 0, 0), direction: QuadDirection::BACK } ,
+        OffsetAndDirection { offset: IVec3::new(0, 0, -1), direction: QuadDirection::LEFT } ,
+        OffsetAndDirection { offset: IVec3::new(0, 0, 1), direction: QuadDirection::RIGHT } ,
+        OffsetAndDirection { offset: IVec3::new(0, -1,  0),direction:  QuadDirection::BOTTOM } ,
+        OffsetAndDirection { offset: IVec3::new(0, 1,0), direction: QuadDirection::TOP } ,
+    ];
+
+    for offset_and_direction in offset_and_directions {
+        let neighbour_voxel_location = *center_voxel + offset_and_direction.offset;
+        let neighbour_voxel = chunk.get_voxel_or_border(&neighbour_voxel_location);
+
+        if !layer.is_hidden_by(&neighbour_voxel, block_registry) {
+            voxel_quads.push(generate_voxel_quad(offset_and_direction.direction, voxel.block_id, x, y, z));
+        }
+    }
+}
+
+// Every voxel on the outside of the chunk, each visited once
+fn chunk_shell(size: &IVec3) -> Vec<IVec3> {
+    let mut shell = Vec::new();
+    for z in 0..size.z {
+        for y in 0..size.y {
+            if z == 0 || z == size.z - 1 || y == 0 || y == size.y - 1 {
+                for x in 0..size.x {
+                    shell.push(IVec3::new(x, y, z));
+                }
+            } else {
+                shell.push(IVec3::new(0, y, z));
+                if size.x > 1 {
+                    shell.push(IVec3::new(size.x - 1, y, z));
+                }
+            }
+        }
+    }
+    shell
+}
+
+fn generate_voxel_quad(quad_direction: QuadDirection, block_id: BlockId, x: i32, y: i32, z: i32) -> VoxelQuad {
     VoxelQuad {
         // Note Y <=> Z swapped to work, cba to work out why
         quad: generate_quad(quad_direction, x as f32, y as f32, z as f32),
+        block_id,
         x,
         y,
         z,
@@ -57,8 +57,6 @@ use util::movement_axis;
 mod cam2d;
 mod util;
 
-pub use cam2d::FlyCamera2d;
-
 /// A set of options for initializing a FlyCamera.
 /// Attach this component to a [`Camera3dBundle`](https://docs.rs/bevy/0.4.0/bevy/prelude/struct.Camera3dBundle.html) bundle to control it with your mouse and keyboard.
 /// # Example
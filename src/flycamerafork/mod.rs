@@ -1 +1,3 @@
-pub(crate) mod fly_camera;
+// A fork of bevy_fly_camera, kept as upstream wrote it
+#[allow(clippy::tabs_in_doc_comments, clippy::empty_line_after_doc_comments, clippy::let_and_return)]
+pub mod fly_camera;
\ No newline at end of file
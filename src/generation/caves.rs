@@ -0,0 +1,88 @@
+use bevy::math::IVec3;
+use crate::generation::fractal_noise::FractalNoise;
+use crate::terrain_noise::TerrainNoise;
+
+// Over how many voxels the caves close up towards min_height and max_height, so they don't end in a flat cut
+const CAVE_TAPER: f64 = 8.0;
+
+// Carves caves out of terrain that has already been generated. Only depends on the position, so a voxel is carved
+// the same whichever chunk generates it
+pub struct CaveCarver {
+    // Nothing at or below min_height is carved, leaving a solid floor at the bottom of the world
+    pub min_height: i32,
+    pub max_height: i32,
+    // Large open caverns, where this noise is above cheese_threshold
+    pub cheese_noise: FractalNoise,
+    pub cheese_threshold: f64,
+    // Long winding tunnels, where both noises are within noodle_width of their middle. Each noise is 0.5 on a
+    // twisting sheet and the tunnels follow where the two sheets cross
+    pub noodle_noises: [FractalNoise; 2],
+    pub noodle_width: f64,
+}
+
+impl Default for CaveCarver {
+    fn default() -> Self {
+        CaveCarver {
+            min_height: -28,
+            max_height: 48,
+            cheese_noise: FractalNoise::fbm(0.03, 2).with_seed_offset(20_000.0),
+            cheese_threshold: 0.72,
+            noodle_noises: [
+                FractalNoise::fbm(0.015, 1).with_seed_offset(30_000.0),
+                FractalNoise::fbm(0.015, 1).with_seed_offset(40_000.0),
+            ],
+            noodle_width: 0.03,
+        }
+    }
+}
+
+impl CaveCarver {
+    // Whether the voxel at global_xyz is hollowed out
+    pub fn carves(&self, terrain_noise: &TerrainNoise, global_xyz: &IVec3) -> bool {
+        if global_xyz.y <= self.min_height || global_xyz.y >= self.max_height {
+            return false;
+        }
+
+        // 0 at the depth limits, 1 once CAVE_TAPER inside them
+        let taper = ((global_xyz.y - self.min_height).min(self.max_height - global_xyz.y) as f64 / CAVE_TAPER).min(1.0);
+        let (x, y, z) = (global_xyz.x as f64, global_xyz.y as f64, global_xyz.z as f64);
+
+        let cheese = self.cheese_noise.sample_3d(terrain_noise, x, y, z);
+        if cheese > self.cheese_threshold + (1.0 - taper) * (1.0 - self.cheese_threshold) {
+            return true;
+        }
+
+        let noodle_width = self.noodle_width * taper;
+        self.noodle_noises.iter()
+            .all(|noise| (noise.sample_3d(terrain_noise, x, y, z) - 0.5).abs() < noodle_width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::caves::CaveCarver;
+    use crate::terrain_noise::TerrainNoise;
+    use bevy::math::IVec3;
+
+    #[test]
+    fn caves_stay_within_depth_limits_test() {
+        let terrain_noise = TerrainNoise::new(42);
+        let cave_carver = CaveCarver::default();
+
+        let mut carved = 0;
+        for x in (0..256).step_by(2) {
+            for z in (0..256).step_by(2) {
+                for y in cave_carver.min_height - 8..cave_carver.max_height + 8 {
+                    if cave_carver.carves(&terrain_noise, &IVec3::new(x, y, z)) {
+                        assert!(y > cave_carver.min_height && y < cave_carver.max_height);
+                        carved += 1;
+                    }
+                }
+            }
+        }
+
+        // Some caves, but most of the ground is left
+        let total = 128 * 128 * (cave_carver.max_height - cave_carver.min_height - 1);
+        assert!(carved > total / 100 && carved < total / 4, "{} of {} carved", carved, total);
+    }
+}
@@ -0,0 +1,122 @@
+use bevy::math::IVec3;
+use crate::block;
+use crate::generation::random::hash_position_f64;
+use crate::generation::TerrainGenerator;
+use crate::voxel::{BlockId, Voxel};
+
+// Generators for testing the mesher and chunk streaming rather than for playing in. Both only fill the layers
+// 0..height so the world above and below stays cheap
+
+// Alternating solid and air voxels, the worst case for the number of faces in a chunk
+pub struct CheckerboardGenerator {
+    pub block_id: BlockId,
+    pub height: i32,
+}
+
+impl Default for CheckerboardGenerator {
+    fn default() -> Self {
+        CheckerboardGenerator {
+            block_id: block::STONE,
+            height: 32,
+        }
+    }
+}
+
+impl TerrainGenerator for CheckerboardGenerator {
+    fn generate_voxel(&self, global_xyz: &IVec3) -> Voxel {
+        if global_xyz.y < 0 || global_xyz.y >= self.height {
+            return Voxel::AIR;
+        }
+
+        if (global_xyz.x + global_xyz.y + global_xyz.z).rem_euclid(2) == 0 {
+            Voxel::new(self.block_id)
+        } else {
+            Voxel::AIR
+        }
+    }
+
+    fn uniform_chunk_voxel(&self, location: &IVec3, size: &IVec3) -> Option<Voxel> {
+        outside_height(location, size, self.height)
+    }
+}
+
+// Each voxel is solid with a fixed chance, seeded so the same world comes back every time
+pub struct SparseGenerator {
+    pub seed: i64,
+    pub block_id: BlockId,
+    pub solid_chance: f64,
+    pub height: i32,
+}
+
+impl SparseGenerator {
+    pub fn new(seed: i64) -> SparseGenerator {
+        SparseGenerator {
+            seed,
+            block_id: block::STONE,
+            solid_chance: 0.3,
+            height: 32,
+        }
+    }
+}
+
+impl TerrainGenerator for SparseGenerator {
+    fn generate_voxel(&self, global_xyz: &IVec3) -> Voxel {
+        if global_xyz.y < 0 || global_xyz.y >= self.height {
+            return Voxel::AIR;
+        }
+
+        if hash_position_f64(self.seed, global_xyz) < self.solid_chance {
+            Voxel::new(self.block_id)
+        } else {
+            Voxel::AIR
+        }
+    }
+
+    fn uniform_chunk_voxel(&self, location: &IVec3, size: &IVec3) -> Option<Voxel> {
+        outside_height(location, size, self.height)
+    }
+}
+
+fn outside_height(location: &IVec3, size: &IVec3, height: i32) -> Option<Voxel> {
+    let bottom = location.y * size.y;
+    if bottom >= height || bottom + size.y <= 0 {
+        Some(Voxel::AIR)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::debug::{CheckerboardGenerator, SparseGenerator};
+    use crate::generation::TerrainGenerator;
+    use crate::voxel::Voxel;
+    use bevy::math::IVec3;
+
+    #[test]
+    fn checkerboard_test() {
+        let generator = CheckerboardGenerator::default();
+        assert_ne!(generator.generate_voxel(&IVec3::new(0, 0, 0)), Voxel::AIR);
+        assert_eq!(generator.generate_voxel(&IVec3::new(-1, 0, 0)), Voxel::AIR);
+        assert_ne!(generator.generate_voxel(&IVec3::new(-1, 1, 0)), Voxel::AIR);
+        assert_eq!(generator.generate_voxel(&IVec3::new(0, 32, 0)), Voxel::AIR);
+    }
+
+    #[test]
+    fn sparse_is_deterministic_test() {
+        let size = IVec3::new(16, 16, 16);
+        let a = SparseGenerator::new(3).generate_chunk(IVec3::new(1, 0, 2), size);
+        let b = SparseGenerator::new(3).generate_chunk(IVec3::new(1, 0, 2), size);
+        let c = SparseGenerator::new(4).generate_chunk(IVec3::new(1, 0, 2), size);
+
+        let mut solid = 0;
+        let mut differs = false;
+        for n in 0..a.voxels.len() {
+            assert_eq!(a.voxels.get(n), b.voxels.get(n));
+            differs |= a.voxels.get(n) != c.voxels.get(n);
+            solid += (a.voxels.get(n) != Voxel::AIR) as usize;
+        }
+        assert!(differs);
+        assert!((solid as f64 / a.voxels.len() as f64 - 0.3).abs() < 0.05);
+    }
+}
@@ -0,0 +1,177 @@
+use bevy::math::IVec3;
+use crate::block;
+use crate::chunk::Chunk;
+use crate::generation::random::{hash_position, split_mix};
+use crate::voxel::Voxel;
+
+// A voxel a decoration wants to place, in world space so it can land in any chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecorationWrite {
+    pub world_xyz: IVec3,
+    pub voxel: Voxel,
+}
+
+// Decorations only fill air or thinner decorations: leaves give way to logs, and nothing replaces the terrain. The
+// stronger voxel always wins whichever order writes arrive in, which keeps chunks the same however they load
+fn decoration_rank(voxel: Voxel) -> (u8, u16) {
+    let rank = match voxel.block_id {
+        block::AIR => 0,
+        block::LEAVES => 1,
+        block::LOG => 2,
+        _ => 3
+    };
+    (rank, voxel.block_id.0)
+}
+
+pub fn merge_decoration(current: Voxel, write: Voxel) -> Voxel {
+    if decoration_rank(write) > decoration_rank(current) { write } else { current }
+}
+
+// Places trees and boulders on the surface of a generated chunk. Each chunk decorates its own columns, decorations
+// that reach past its faces are handed back as writes for the ChunkPipeline to apply to the neighbours
+pub struct Decorator {
+    seed: i64,
+    // Tries per chunk, a try only places something if its column has a surface in the chunk
+    pub trees_per_chunk: u32,
+    pub boulders_per_chunk: u32,
+}
+
+impl Decorator {
+    pub fn new(seed: i64) -> Decorator {
+        Decorator {
+            seed,
+            trees_per_chunk: 4,
+            boulders_per_chunk: 1,
+        }
+    }
+
+    // Writes the parts of decorations inside the chunk and returns the rest
+    pub fn decorate(&self, chunk: &mut Chunk) -> Vec<DecorationWrite> {
+        let offset = chunk.location * chunk.size;
+        let mut hash = hash_position(self.seed, &chunk.location);
+        let mut next = |len: i32| {
+            hash = split_mix(hash);
+            (hash % len as u64) as i32
+        };
+
+        let mut writes = Vec::new();
+        for n in 0..self.trees_per_chunk + self.boulders_per_chunk {
+            let column = IVec3::new(next(chunk.size.x), 0, next(chunk.size.z));
+            let trunk_height = 4 + next(3);
+            let ground = match surface(chunk, column.x, column.z) {
+                Some(ground) => ground,
+                None => continue
+            };
+            let ground_block = chunk.get_voxel(&ground).block_id;
+            let ground_xyz = offset + ground;
+
+            if n < self.trees_per_chunk {
+                if [block::GRASS, block::LIGHT_GRASS, block::DARK_GRASS].contains(&ground_block) {
+                    tree(&ground_xyz, trunk_height, &mut writes);
+                }
+            } else if ground_block != block::SNOW {
+                blob(&(ground_xyz + IVec3::Y), 1.5, Voxel::new(block::STONE), &mut writes);
+            }
+        }
+
+        writes.retain(|write| {
+            let local_xyz = write.world_xyz - offset;
+            if chunk.contains(&local_xyz) {
+                let current = chunk.get_voxel(&local_xyz);
+                chunk.set_voxel(&local_xyz, merge_decoration(current, write.voxel));
+                false
+            } else {
+                true
+            }
+        });
+        writes
+    }
+}
+
+// Air and water aren't solid, so nothing stands on them
+fn is_ground(voxel: Voxel) -> bool {
+    !matches!(voxel.block_id, block::AIR | block::WATER)
+}
+
+// The top solid voxel of a column, if it is inside the chunk with air above it. Flooded ground has no surface
+fn surface(chunk: &Chunk, x: i32, z: i32) -> Option<IVec3> {
+    (0..chunk.size.y).rev()
+        .map(|y| IVec3::new(x, y, z))
+        .find(|xyz| is_ground(chunk.get_voxel(xyz)))
+        .filter(|xyz| chunk.get_voxel_or_border(&(*xyz + IVec3::Y)) == Voxel::AIR)
+}
+
+fn tree(ground_xyz: &IVec3, trunk_height: i32, writes: &mut Vec<DecorationWrite>) {
+    let top = *ground_xyz + IVec3::new(0, trunk_height, 0);
+    blob(&top, 2.5, Voxel::new(block::LEAVES), writes);
+    for y in 1..=trunk_height {
+        writes.push(DecorationWrite { world_xyz: *ground_xyz + IVec3::new(0, y, 0), voxel: Voxel::new(block::LOG) });
+    }
+}
+
+fn blob(centre: &IVec3, radius: f32, voxel: Voxel, writes: &mut Vec<DecorationWrite>) {
+    let extent = radius.ceil() as i32;
+    for x in -extent..=extent {
+        for y in -extent..=extent {
+            for z in -extent..=extent {
+                let from_centre = IVec3::new(x, y, z);
+                if from_centre.as_vec3().length_squared() <= radius * radius {
+                    writes.push(DecorationWrite { world_xyz: *centre + from_centre, voxel });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block;
+    use crate::generation::decoration::{Decorator, merge_decoration, surface};
+    use crate::generation::superflat::{SuperflatGenerator, SuperflatLayer};
+    use crate::generation::TerrainGenerator;
+    use crate::voxel::Voxel;
+    use bevy::math::IVec3;
+
+    #[test]
+    fn merge_is_order_independent_test() {
+        let voxels = [Voxel::AIR, Voxel::new(block::LEAVES), Voxel::new(block::LOG), Voxel::new(block::STONE), Voxel::new(block::GRASS)];
+        for a in voxels {
+            for b in voxels {
+                for c in voxels {
+                    assert_eq!(merge_decoration(merge_decoration(a, b), c), merge_decoration(merge_decoration(a, c), b));
+                }
+            }
+        }
+        assert_eq!(merge_decoration(Voxel::new(block::GRASS), Voxel::new(block::LOG)), Voxel::new(block::GRASS));
+    }
+
+    #[test]
+    fn decorations_are_deterministic_test() {
+        let generator = SuperflatGenerator::default();
+        let decorator = Decorator::new(42);
+        let size = IVec3::new(16, 16, 16);
+
+        let mut chunk = generator.generate_chunk(IVec3::ZERO, size);
+        let writes = decorator.decorate(&mut chunk);
+        let mut again = generator.generate_chunk(IVec3::ZERO, size);
+        assert_eq!(decorator.decorate(&mut again), writes);
+
+        // The grass is at y 11, so trees grow out of the top of the chunk
+        assert!(writes.iter().any(|write| write.world_xyz.y >= size.y && write.voxel == Voxel::new(block::LOG)));
+        assert!(writes.iter().all(|write| !chunk.contains(&write.world_xyz)));
+    }
+
+    #[test]
+    fn flooded_columns_have_no_surface_test() {
+        let mut generator = SuperflatGenerator::default();
+        let size = IVec3::new(16, 16, 16);
+        let dry = generator.generate_chunk(IVec3::ZERO, size);
+        assert_eq!(surface(&dry, 3, 3), Some(IVec3::new(3, 11, 3)));
+
+        // The grass under the water isn't the surface, and neither is the water
+        generator.layers.push(SuperflatLayer { block_id: block::WATER, thickness: 2 });
+        let mut flooded = generator.generate_chunk(IVec3::ZERO, size);
+        assert_eq!(surface(&flooded, 3, 3), None);
+        assert!(Decorator::new(42).decorate(&mut flooded).is_empty());
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use bevy::math::IVec2;
+use crate::generation::random::split_mix;
+
+// Eroded regions kept for reuse, past this the least recently used are dropped
+const CACHED_REGIONS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErosionError {
+    // Regions must be at least twice the margin, and the margin at least 1
+    InvalidRegions { region_size: i32, margin: i32 },
+}
+
+impl fmt::Display for ErosionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErosionError::InvalidRegions { region_size, margin } => write!(f,
+                "erosion regions of {} columns with a margin of {} are invalid, the margin must be at least 1 and the regions at least twice the margin",
+                region_size, margin),
+        }
+    }
+}
+
+impl std::error::Error for ErosionError {}
+
+// Region change maps, with the regions in the order they were last used
+#[derive(Default)]
+struct RegionCache {
+    regions: HashMap<IVec2, Arc<Vec<f32>>>,
+    recently_used: VecDeque<IVec2>,
+}
+
+impl RegionCache {
+    fn get(&mut self, region: &IVec2) -> Option<Arc<Vec<f32>>> {
+        let changes = self.regions.get(region)?.clone();
+        self.touch(region);
+        Some(changes)
+    }
+
+    fn insert(&mut self, region: IVec2, changes: Arc<Vec<f32>>) {
+        if self.regions.insert(region, changes).is_some() {
+            self.touch(&region);
+            return;
+        }
+        self.recently_used.push_back(region);
+        while self.regions.len() > CACHED_REGIONS {
+            let oldest = self.recently_used.pop_front().unwrap();
+            self.regions.remove(&oldest);
+        }
+    }
+
+    fn touch(&mut self, region: &IVec2) {
+        if let Some(position) = self.recently_used.iter().position(|used| used == region) {
+            self.recently_used.remove(position);
+        }
+        self.recently_used.push_back(*region);
+    }
+}
+
+// Wears the terrain down the way water and gravity would, on a heightmap of the ground surface. The world is split
+// into square regions which are each eroded on their own, seeded from their position so a region always erodes the
+// same. Each region is eroded with a margin around it that overlaps its neighbours, and where regions overlap their
+// results are blended, so the change in height is continuous across regions and doesn't depend on chunk order
+pub struct Erosion {
+    seed: i64,
+    // In columns, set with with_regions
+    region_size: i32,
+    margin: i32,
+    // Hydraulic erosion. Droplets run downhill picking up sediment while they speed up, and drop it as they slow
+    pub droplets_per_region: u32,
+    pub droplet_lifetime: u32,
+    // How much a droplet keeps its direction rather than following the slope, 0 to 1
+    pub inertia: f32,
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erode_speed: f32,
+    pub deposit_speed: f32,
+    pub evaporate_speed: f32,
+    pub gravity: f32,
+    // Thermal erosion. Ground steeper than talus voxels per column slumps down onto its neighbours
+    pub thermal_iterations: u32,
+    pub talus: f32,
+    pub thermal_rate: f32,
+    // The most a column is raised or lowered, in voxels
+    pub max_change: i32,
+    cache: Mutex<RegionCache>,
+}
+
+impl Erosion {
+    pub fn new(seed: i64) -> Erosion {
+        Erosion {
+            seed,
+            region_size: 64,
+            margin: 16,
+            droplets_per_region: 6000,
+            droplet_lifetime: 48,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporate_speed: 0.02,
+            gravity: 4.0,
+            thermal_iterations: 8,
+            talus: 1.5,
+            thermal_rate: 0.25,
+            max_change: 8,
+            cache: Mutex::new(RegionCache::default()),
+        }
+    }
+
+    // Regions of region_size by region_size columns, each eroded with margin columns of its neighbours around it
+    pub fn with_regions(mut self, region_size: i32, margin: i32) -> Result<Erosion, ErosionError> {
+        if margin < 1 || region_size < 2 * margin {
+            return Err(ErosionError::InvalidRegions { region_size, margin });
+        }
+        self.region_size = region_size;
+        self.margin = margin;
+        self.cache = Mutex::new(RegionCache::default());
+        Ok(self)
+    }
+
+    // How many voxels the column at x, z is raised by, negative where it is worn away. surface gives the height of
+    // the uneroded ground in any column
+    pub fn height_change<F: Fn(i32, i32) -> f32>(&self, x: i32, z: i32, surface: F) -> i32 {
+        let region = IVec2::new(x.div_euclid(self.region_size), z.div_euclid(self.region_size));
+
+        let mut change = 0.0;
+        for region_x in region.x - 1..=region.x + 1 {
+            for region_z in region.y - 1..=region.y + 1 {
+                let weight = self.region_weight(x, region_x) * self.region_weight(z, region_z);
+                if weight <= 0.0 {
+                    continue;
+                }
+                let region = IVec2::new(region_x, region_z);
+                let changes = self.region_changes(region, &surface);
+                let local = IVec2::new(x, z) - self.region_origin(region);
+                change += weight * changes[(local.y * self.region_side() + local.x) as usize];
+            }
+        }
+
+        (change.round() as i32).clamp(-self.max_change, self.max_change)
+    }
+
+    // Rises from 0 at the edge of the region's margin to 1 at the other side of it, so overlapping regions add up to 1
+    fn region_weight(&self, coordinate: i32, region: i32) -> f32 {
+        let start = region * self.region_size - self.margin;
+        let end = (region + 1) * self.region_size + self.margin;
+        let inside = ((coordinate - start) as f32 + 0.5).min((end - coordinate) as f32 - 0.5);
+        (inside / (2 * self.margin) as f32).clamp(0.0, 1.0)
+    }
+
+    // The first column of the region with its margin
+    fn region_origin(&self, region: IVec2) -> IVec2 {
+        region * self.region_size - IVec2::splat(self.margin)
+    }
+
+    fn region_side(&self) -> i32 {
+        self.region_size + 2 * self.margin
+    }
+
+    // The change in height of every column of the region and its margin
+    fn region_changes<F: Fn(i32, i32) -> f32>(&self, region: IVec2, surface: &F) -> Arc<Vec<f32>> {
+        if let Some(changes) = self.cache.lock().unwrap().get(&region) {
+            return changes.clone();
+        }
+
+        // Eroded without holding the lock, two threads may both erode a region but they get the same result
+        let side = self.region_side();
+        let origin = self.region_origin(region);
+        let before: Vec<f32> = (0..side * side)
+            .map(|n| surface(origin.x + n % side, origin.y + n / side))
+            .collect();
+        let mut after = before.clone();
+        let seed = split_mix(self.seed as u64 ^ split_mix((region.x as u32 as u64) << 32 | region.y as u32 as u64));
+        self.erode(&mut after, side as usize, seed);
+
+        let changes = Arc::new(after.iter().zip(before.iter()).map(|(after, before)| after - before).collect::<Vec<f32>>());
+        self.cache.lock().unwrap().insert(region, changes.clone());
+        changes
+    }
+
+    // Erodes a square heightmap of side by side columns, row by row
+    pub fn erode(&self, heights: &mut [f32], side: usize, seed: u64) {
+        // Droplets need a column to run to
+        if side < 2 {
+            return;
+        }
+        let mut hash = seed;
+        for _ in 0..self.droplets_per_region {
+            // In 1/1024ths of a column, which keeps droplets off the far edges
+            let mut next = || {
+                hash = split_mix(hash);
+                (hash % ((side as u64 - 1) * 1024)) as f32 / 1024.0
+            };
+            let (x, z) = (next(), next());
+            self.run_droplet(heights, side, x, z);
+        }
+
+        for _ in 0..self.thermal_iterations {
+            self.slump(heights, side);
+        }
+    }
+
+    fn run_droplet(&self, heights: &mut [f32], side: usize, mut x: f32, mut z: f32) {
+        let (mut direction_x, mut direction_z) = (0.0, 0.0);
+        let mut speed = 1.0;
+        let mut water = 1.0;
+        let mut sediment = 0.0;
+
+        for _ in 0..self.droplet_lifetime {
+            let (cell_x, cell_z) = (x as usize, z as usize);
+            let (height, gradient_x, gradient_z) = height_and_gradient(heights, side, x, z);
+
+            direction_x = direction_x * self.inertia - gradient_x * (1.0 - self.inertia);
+            direction_z = direction_z * self.inertia - gradient_z * (1.0 - self.inertia);
+            let length = (direction_x * direction_x + direction_z * direction_z).sqrt();
+            if length < f32::EPSILON {
+                break;
+            }
+            let (offset_x, offset_z) = (x - cell_x as f32, z - cell_z as f32);
+            x += direction_x / length;
+            z += direction_z / length;
+            // Droplets that leave the heightmap take their sediment with them
+            if x < 0.0 || z < 0.0 || x >= (side - 1) as f32 || z >= (side - 1) as f32 {
+                break;
+            }
+
+            let height_difference = height_and_gradient(heights, side, x, z).0 - height;
+            let capacity = (-height_difference * speed * water * self.sediment_capacity).max(self.min_sediment_capacity);
+
+            if sediment > capacity || height_difference > 0.0 {
+                // Going uphill fills the pit it left, otherwise drop what it can't carry
+                let deposit = if height_difference > 0.0 {
+                    height_difference.min(sediment)
+                } else {
+                    (sediment - capacity) * self.deposit_speed
+                };
+                sediment -= deposit;
+                spread(heights, side, cell_x, cell_z, offset_x, offset_z, deposit);
+            } else {
+                // Never digs deeper than the ground it is running down to
+                let erode = ((capacity - sediment) * self.erode_speed).min(-height_difference);
+                sediment += erode;
+                spread(heights, side, cell_x, cell_z, offset_x, offset_z, -erode);
+            }
+
+            speed = (speed * speed - height_difference * self.gravity).max(0.0).sqrt();
+            water *= 1.0 - self.evaporate_speed;
+        }
+    }
+
+    // Moves ground from columns steeper than talus onto their lower neighbours. Every column is compared against the
+    // heights from before the pass, so the result doesn't depend on the order columns are visited
+    fn slump(&self, heights: &mut [f32], side: usize) {
+        let before = heights.to_vec();
+        for z in 0..side {
+            for x in 0..side {
+                let index = z * side + x;
+                for (neighbour_x, neighbour_z) in [(x.wrapping_sub(1), z), (x + 1, z), (x, z.wrapping_sub(1)), (x, z + 1)] {
+                    if neighbour_x >= side || neighbour_z >= side {
+                        continue;
+                    }
+                    let neighbour = neighbour_z * side + neighbour_x;
+                    let difference = before[index] - before[neighbour];
+                    if difference > self.talus {
+                        // Halved as the neighbour also moves, and kept small as a column can give to all four
+                        let amount = (difference - self.talus) * self.thermal_rate / 2.0;
+                        heights[index] -= amount;
+                        heights[neighbour] += amount;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Bilinear height at x, z and its slope, x and z must be at least a column inside the far edges
+fn height_and_gradient(heights: &[f32], side: usize, x: f32, z: f32) -> (f32, f32, f32) {
+    let (cell_x, cell_z) = (x as usize, z as usize);
+    let (offset_x, offset_z) = (x - cell_x as f32, z - cell_z as f32);
+    let index = cell_z * side + cell_x;
+    let (north_west, north_east) = (heights[index], heights[index + 1]);
+    let (south_west, south_east) = (heights[index + side], heights[index + side + 1]);
+
+    let gradient_x = (north_east - north_west) * (1.0 - offset_z) + (south_east - south_west) * offset_z;
+    let gradient_z = (south_west - north_west) * (1.0 - offset_x) + (south_east - north_east) * offset_x;
+    let height = north_west * (1.0 - offset_x) * (1.0 - offset_z) + north_east * offset_x * (1.0 - offset_z)
+        + south_west * (1.0 - offset_x) * offset_z + south_east * offset_x * offset_z;
+    (height, gradient_x, gradient_z)
+}
+
+// Adds amount to the four columns around a point, weighted by how close it is to each
+fn spread(heights: &mut [f32], side: usize, cell_x: usize, cell_z: usize, offset_x: f32, offset_z: f32, amount: f32) {
+    let index = cell_z * side + cell_x;
+    heights[index] += amount * (1.0 - offset_x) * (1.0 - offset_z);
+    heights[index + 1] += amount * offset_x * (1.0 - offset_z);
+    heights[index + side] += amount * (1.0 - offset_x) * offset_z;
+    heights[index + side + 1] += amount * offset_x * offset_z;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::erosion::{CACHED_REGIONS, Erosion, ErosionError};
+    use bevy::math::IVec2;
+
+    // A cone with ripples, so droplets have somewhere to run
+    fn hill(x: i32, z: i32) -> f32 {
+        let distance = ((x * x + z * z) as f32).sqrt();
+        (40.0 - distance * 0.8).max(0.0) + (x as f32 * 0.7).sin() * 2.0 + (z as f32 * 0.4).cos() * 2.0
+    }
+
+    fn test_erosion() -> Erosion {
+        let mut erosion = Erosion::new(42).with_regions(32, 8).unwrap();
+        erosion.droplets_per_region = 2000;
+        erosion
+    }
+
+    #[test]
+    fn invalid_regions_test() {
+        for (region_size, margin) in [(0, 0), (1, 0), (8, 0), (15, 8), (-4, 2)] {
+            assert_eq!(Erosion::new(42).with_regions(region_size, margin).err(),
+                       Some(ErosionError::InvalidRegions { region_size, margin }));
+        }
+        assert!(Erosion::new(42).with_regions(2, 1).is_ok());
+
+        // Too small to run a droplet on, which leaves it alone
+        let mut heights = vec![5.0];
+        Erosion::new(42).erode(&mut heights, 1, 7);
+        assert_eq!(heights, vec![5.0]);
+    }
+
+    #[test]
+    fn cache_drops_least_recently_used_test() {
+        let mut erosion = Erosion::new(42).with_regions(4, 2).unwrap();
+        erosion.droplets_per_region = 0;
+        erosion.thermal_iterations = 0;
+
+        erosion.height_change(2, 2, hill);
+        for x in 1..CACHED_REGIONS as i32 + 8 {
+            erosion.height_change(x * 4 + 2, 2, hill);
+            // Kept in use, so it is never the oldest
+            erosion.height_change(2, 2, hill);
+        }
+
+        let cache = erosion.cache.lock().unwrap();
+        assert_eq!(cache.regions.len(), CACHED_REGIONS);
+        assert_eq!(cache.recently_used.len(), CACHED_REGIONS);
+        assert!(cache.regions.contains_key(&IVec2::new(0, 0)));
+        assert!(cache.regions.contains_key(&IVec2::new(CACHED_REGIONS as i32 + 7, 0)));
+        assert!(!cache.regions.contains_key(&IVec2::new(2, 0)));
+    }
+
+    #[test]
+    fn erosion_wears_hills_down_test() {
+        let erosion = test_erosion();
+        let side = 48;
+        let before: Vec<f32> = (0..side * side).map(|n| hill(n % side - 24, n / side - 24)).collect();
+
+        let mut after = before.clone();
+        erosion.erode(&mut after, side as usize, 7);
+        let mut again = before.clone();
+        erosion.erode(&mut again, side as usize, 7);
+        assert_eq!(after, again);
+
+        // Worn from the slopes and dropped lower down. Nothing is added, only what droplets carry off the edges is lost
+        assert!(after.iter().zip(before.iter()).any(|(after, before)| after < &(before - 0.5)));
+        assert!(after.iter().zip(before.iter()).any(|(after, before)| after > &(before + 0.5)));
+        let total = |heights: &[f32]| heights.iter().sum::<f32>();
+        assert!(total(&after) <= total(&before) + 1.0);
+        assert!(total(&after) > total(&before) * 0.8);
+    }
+
+    #[test]
+    fn regions_blend_without_seams_test() {
+        let erosion = test_erosion();
+        for coordinate in -40..40 {
+            let total: f32 = (-3..=3).map(|region| erosion.region_weight(coordinate, region)).sum();
+            assert!((total - 1.0).abs() < 1e-5);
+        }
+
+        // Asked for in a different order, or by a fresh Erosion with nothing cached, a column changes the same
+        let changes: Vec<i32> = (-40..40).map(|x| erosion.height_change(x, 5, hill)).collect();
+        let reversed: Vec<i32> = (-40..40).rev().map(|x| test_erosion().height_change(x, 5, hill)).collect();
+        assert_eq!(changes, reversed.into_iter().rev().collect::<Vec<_>>());
+        assert!(changes.iter().any(|change| *change != 0));
+
+        // Across the edges of the regions at -32, 0 and 32 the ground doesn't jump
+        for pair in changes.windows(2) {
+            assert!((pair[0] - pair[1]).abs() <= 3, "{:?}", changes);
+        }
+    }
+}
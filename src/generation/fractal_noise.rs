@@ -0,0 +1,180 @@
+use serde::Deserialize;
+use crate::terrain_noise::TerrainNoise;
+
+// Apart from the seed offset, so octaves of one noise don't line up with each other at the origin
+const OCTAVE_OFFSET: f64 = 1000.0;
+// Where the two warp fields sample, away from the noise they warp
+const WARP_OFFSET_X: f64 = 5000.0;
+const WARP_OFFSET_Z: f64 = 9000.0;
+
+// How each octave's noise is shaped before it is summed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FractalKind {
+    // Fractal Brownian motion, the octaves summed as they are. Rolling hills
+    Fbm,
+    // 1 - |noise| squared, sharp creases where the noise crosses 0. Mountain ridges
+    Ridged,
+    // |noise|, rounded lumps with sharp valleys between them. Dunes and clouds
+    Billow,
+}
+
+// Moves the point a noise is sampled at by another, slower noise, which bends and swirls its features
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DomainWarp {
+    // How far a point can move, in voxels
+    pub amplitude: f64,
+    pub scale: f64,
+    pub octaves: u32,
+}
+
+// Several octaves of simplex noise, each lacunarity times the frequency and persistence times the amplitude of the
+// last. Samples are 0 to 1 whatever the kind
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractalNoise {
+    pub kind: FractalKind,
+    // Frequency of the first octave, in cycles per voxel
+    pub scale: f64,
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub persistence: f64,
+    // Moves where this noise samples the shared simplex noise, so two noises with the same settings differ
+    pub seed_offset: f64,
+    pub warp: Option<DomainWarp>,
+}
+
+impl FractalNoise {
+    pub fn new(kind: FractalKind, scale: f64, octaves: u32) -> FractalNoise {
+        FractalNoise {
+            kind,
+            scale,
+            octaves,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            seed_offset: 0.0,
+            warp: None,
+        }
+    }
+
+    pub fn fbm(scale: f64, octaves: u32) -> FractalNoise {
+        FractalNoise::new(FractalKind::Fbm, scale, octaves)
+    }
+
+    pub fn ridged(scale: f64, octaves: u32) -> FractalNoise {
+        FractalNoise::new(FractalKind::Ridged, scale, octaves)
+    }
+
+    pub fn billow(scale: f64, octaves: u32) -> FractalNoise {
+        FractalNoise::new(FractalKind::Billow, scale, octaves)
+    }
+
+    pub fn with_seed_offset(mut self, seed_offset: f64) -> FractalNoise {
+        self.seed_offset = seed_offset;
+        self
+    }
+
+    pub fn with_warp(mut self, warp: DomainWarp) -> FractalNoise {
+        self.warp = Some(warp);
+        self
+    }
+
+    pub fn sample_2d(&self, terrain_noise: &TerrainNoise, x: f64, z: f64) -> f64 {
+        let (x, z) = match self.warp {
+            Some(warp) => {
+                let warp_noise = self.warp_noise(warp);
+                (x + warp.amplitude * (warp_noise.sample_2d(terrain_noise, x + WARP_OFFSET_X, z) * 2.0 - 1.0),
+                 z + warp.amplitude * (warp_noise.sample_2d(terrain_noise, x, z + WARP_OFFSET_Z) * 2.0 - 1.0))
+            },
+            None => (x, z)
+        };
+
+        self.sum_octaves(|frequency, offset| terrain_noise.eval_2d(x * frequency + offset, z * frequency + offset))
+    }
+
+    pub fn sample_3d(&self, terrain_noise: &TerrainNoise, x: f64, y: f64, z: f64) -> f64 {
+        // Only warped horizontally, so layers stay level
+        let (x, z) = match self.warp {
+            Some(warp) => {
+                let warp_noise = self.warp_noise(warp);
+                (x + warp.amplitude * (warp_noise.sample_3d(terrain_noise, x + WARP_OFFSET_X, y, z) * 2.0 - 1.0),
+                 z + warp.amplitude * (warp_noise.sample_3d(terrain_noise, x, y, z + WARP_OFFSET_Z) * 2.0 - 1.0))
+            },
+            None => (x, z)
+        };
+
+        self.sum_octaves(|frequency, offset| {
+            terrain_noise.eval_3d(x * frequency + offset, y * frequency + offset, z * frequency + offset)
+        })
+    }
+
+    fn warp_noise(&self, warp: DomainWarp) -> FractalNoise {
+        FractalNoise::fbm(warp.scale, warp.octaves).with_seed_offset(self.seed_offset)
+    }
+
+    // eval gets the frequency and offset of an octave and returns simplex noise, -1 to 1
+    fn sum_octaves<F: Fn(f64, f64) -> f64>(&self, eval: F) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut amplitude_sum = 0.0;
+        let mut frequency = self.scale;
+
+        for octave in 0..self.octaves {
+            let noise = eval(frequency, self.seed_offset + octave as f64 * OCTAVE_OFFSET);
+            total += amplitude * match self.kind {
+                FractalKind::Fbm => (noise + 1.0) / 2.0,
+                FractalKind::Ridged => (1.0 - noise.abs()).powi(2),
+                FractalKind::Billow => noise.abs(),
+            };
+            amplitude_sum += amplitude;
+            amplitude *= self.persistence;
+            frequency *= self.lacunarity;
+        }
+
+        (total / amplitude_sum).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::fractal_noise::{DomainWarp, FractalNoise};
+    use crate::terrain_noise::TerrainNoise;
+
+    #[test]
+    fn fractal_noise_range_test() {
+        let terrain_noise = TerrainNoise::new(42);
+        let warp = DomainWarp { amplitude: 20.0, scale: 0.01, octaves: 2 };
+        let noises = [
+            FractalNoise::fbm(0.05, 4),
+            FractalNoise::ridged(0.05, 4),
+            FractalNoise::billow(0.05, 4),
+            FractalNoise::fbm(0.05, 3).with_warp(warp),
+        ];
+
+        for noise in noises.iter() {
+            let mut min: f64 = 1.0;
+            let mut max: f64 = 0.0;
+            for i in 0..2000 {
+                let value = noise.sample_2d(&terrain_noise, i as f64 * 0.37, i as f64 * -1.3);
+                assert_eq!(value, noise.sample_2d(&terrain_noise, i as f64 * 0.37, i as f64 * -1.3));
+                assert!((0.0..=1.0).contains(&value));
+                assert!((0.0..=1.0).contains(&noise.sample_3d(&terrain_noise, i as f64, 3.0, -(i as f64))));
+                min = min.min(value);
+                max = max.max(value);
+            }
+            // Uses a good part of the range rather than sitting in the middle
+            assert!(max - min > 0.4, "{:?} only spans {} to {}", noise.kind, min, max);
+        }
+    }
+
+    #[test]
+    fn seed_offset_and_octaves_change_noise_test() {
+        let terrain_noise = TerrainNoise::new(42);
+        let base = FractalNoise::fbm(0.05, 1);
+        let sample = |noise: FractalNoise| noise.sample_2d(&terrain_noise, 12.5, -7.25);
+
+        assert_ne!(sample(base), sample(base.with_seed_offset(321.0)));
+        assert_ne!(sample(base), sample(FractalNoise::fbm(0.05, 4)));
+        // The first octave alone is plain simplex noise
+        assert_eq!(sample(base), (terrain_noise.eval_2d(12.5 * 0.05, -7.25 * 0.05) + 1.0) / 2.0);
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use std::path::PathBuf;
+use bevy::math::IVec3;
+use image::{ImageBuffer, Luma};
+use serde::{Deserialize, Serialize};
+use crate::block;
+use crate::generation::TerrainGenerator;
+use crate::voxel::Voxel;
+
+// Dirt between the grass and the stone
+const SUBSURFACE_DEPTH: i32 = 3;
+
+// What the ground does past the edges of the image
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeightmapEdge {
+    // The image repeats
+    Tile,
+    // The edge pixels carry on forever
+    Clamp,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightmapSettings {
+    // A grayscale PNG, one pixel per column with x along the image and z down it
+    pub path: PathBuf,
+    pub edge: HeightmapEdge,
+    // Black is the ground at min_height and white at max_height
+    pub min_height: i32,
+    pub max_height: i32,
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> Self {
+        HeightmapSettings {
+            path: PathBuf::from("assets/heightmaps/example.png"),
+            edge: HeightmapEdge::Tile,
+            min_height: 0,
+            max_height: 64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HeightmapError {
+    Image { path: PathBuf, error: image::ImageError },
+    Invalid(String),
+}
+
+impl fmt::Display for HeightmapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeightmapError::Image { path, error } => write!(f, "could not load heightmap {}: {}", path.display(), error),
+            HeightmapError::Invalid(reason) => write!(f, "heightmap is invalid: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for HeightmapError {}
+
+// Terrain from an image, each pixel's brightness is the height of the ground in its column. Grass on top, then dirt,
+// then stone
+pub struct HeightmapGenerator {
+    width: i32,
+    depth: i32,
+    // The y of the top voxel of each column, row by row
+    heights: Vec<i32>,
+    edge: HeightmapEdge,
+    min_height: i32,
+    max_height: i32,
+}
+
+impl HeightmapGenerator {
+    pub fn load(settings: &HeightmapSettings) -> Result<HeightmapGenerator, HeightmapError> {
+        let image = image::open(&settings.path)
+            .map_err(|error| HeightmapError::Image { path: settings.path.clone(), error })?;
+        HeightmapGenerator::from_image(&image.into_luma16(), settings)
+    }
+
+    // 8 bit images are stretched to 16 bits, so either works with the same heights
+    pub fn from_image(image: &ImageBuffer<Luma<u16>, Vec<u16>>, settings: &HeightmapSettings) -> Result<HeightmapGenerator, HeightmapError> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(HeightmapError::Invalid("the image is empty".to_string()));
+        }
+        if settings.max_height <= settings.min_height {
+            return Err(HeightmapError::Invalid("max_height must be above min_height".to_string()));
+        }
+
+        let range = (settings.max_height - settings.min_height) as f64;
+        let heights = image.pixels()
+            .map(|pixel| settings.min_height + (pixel.0[0] as f64 / u16::MAX as f64 * range).round() as i32)
+            .collect();
+
+        Ok(HeightmapGenerator {
+            width: image.width() as i32,
+            depth: image.height() as i32,
+            heights,
+            edge: settings.edge,
+            min_height: settings.min_height,
+            max_height: settings.max_height,
+        })
+    }
+
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        let (x, z) = match self.edge {
+            HeightmapEdge::Tile => (x.rem_euclid(self.width), z.rem_euclid(self.depth)),
+            HeightmapEdge::Clamp => (x.clamp(0, self.width - 1), z.clamp(0, self.depth - 1)),
+        };
+        self.heights[(z * self.width + x) as usize]
+    }
+}
+
+impl TerrainGenerator for HeightmapGenerator {
+    fn generate_voxel(&self, global_xyz: &IVec3) -> Voxel {
+        let height = self.height_at(global_xyz.x, global_xyz.z);
+        if global_xyz.y > height {
+            Voxel::AIR
+        } else if global_xyz.y == height {
+            Voxel::new(block::GRASS)
+        } else if global_xyz.y >= height - SUBSURFACE_DEPTH {
+            Voxel::new(block::DIRT)
+        } else {
+            Voxel::new(block::STONE)
+        }
+    }
+
+    fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        Some(self.height_at(x, z))
+    }
+
+    fn uniform_chunk_voxel(&self, location: &IVec3, size: &IVec3) -> Option<Voxel> {
+        let bottom = location.y * size.y;
+        if bottom > self.max_height {
+            Some(Voxel::AIR)
+        } else if bottom + size.y <= self.min_height - SUBSURFACE_DEPTH {
+            Some(Voxel::new(block::STONE))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Luma};
+    use crate::block;
+    use crate::generation::heightmap::{HeightmapEdge, HeightmapGenerator, HeightmapSettings};
+    use crate::generation::TerrainGenerator;
+    use crate::voxel::Voxel;
+    use bevy::math::IVec3;
+
+    // 4 by 2, getting brighter along x
+    fn test_generator(edge: HeightmapEdge) -> HeightmapGenerator {
+        let image = ImageBuffer::from_fn(4, 2, |x, _| Luma([(x * u16::MAX as u32 / 3) as u16]));
+        let settings = HeightmapSettings { edge, min_height: 10, max_height: 40, ..HeightmapSettings::default() };
+        HeightmapGenerator::from_image(&image, &settings).unwrap()
+    }
+
+    #[test]
+    fn brightness_is_height_test() {
+        let generator = test_generator(HeightmapEdge::Tile);
+        assert_eq!((0..4).map(|x| generator.height_at(x, 0)).collect::<Vec<_>>(), vec![10, 20, 30, 40]);
+
+        assert_eq!(generator.generate_voxel(&IVec3::new(1, 21, 0)), Voxel::AIR);
+        assert_eq!(generator.generate_voxel(&IVec3::new(1, 20, 0)), Voxel::new(block::GRASS));
+        assert_eq!(generator.generate_voxel(&IVec3::new(1, 17, 0)), Voxel::new(block::DIRT));
+        assert_eq!(generator.generate_voxel(&IVec3::new(1, 16, 0)), Voxel::new(block::STONE));
+    }
+
+    #[test]
+    fn edges_tile_or_clamp_test() {
+        let tiled = test_generator(HeightmapEdge::Tile);
+        assert_eq!(tiled.height_at(5, 0), tiled.height_at(1, 0));
+        assert_eq!(tiled.height_at(-1, -3), tiled.height_at(3, 1));
+
+        let clamped = test_generator(HeightmapEdge::Clamp);
+        assert_eq!(clamped.height_at(100, 7), 40);
+        assert_eq!(clamped.height_at(-100, -7), 10);
+
+        // Chunks come out the same as voxel by voxel, past the image too
+        let chunk = clamped.generate_chunk(IVec3::new(-1, 0, 0), IVec3::splat(16));
+        assert_eq!(chunk.get_voxel(&IVec3::new(3, 10, 3)), Voxel::new(block::GRASS));
+        assert_eq!(chunk.get_voxel_or_border(&IVec3::new(16, 11, 0)), Voxel::AIR);
+        assert_eq!(clamped.uniform_chunk_voxel(&IVec3::new(0, 3, 0), &IVec3::splat(16)), Some(Voxel::AIR));
+    }
+
+    #[test]
+    fn shipped_heightmap_loads_test() {
+        let generator = HeightmapGenerator::load(&HeightmapSettings::default()).unwrap();
+        assert!((0..64).any(|x| generator.height_at(x, x) != generator.height_at(0, 0)));
+
+        let missing = HeightmapSettings { path: "assets/heightmaps/missing.png".into(), ..HeightmapSettings::default() };
+        assert!(HeightmapGenerator::load(&missing).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use bevy::math::IVec3;
+use serde::{Deserialize, Serialize};
+use crate::biome::BiomeRegistry;
+use crate::chunk::Chunk;
+use crate::chunk_storage::{ChunkVoxels, PaletteStorage};
+use crate::chunk_utils::voxel_index_to_xyz;
+use crate::generation::decoration::DecorationWrite;
+use crate::generation::erosion::Erosion;
+use crate::generation::debug::{CheckerboardGenerator, SparseGenerator};
+use crate::generation::heightmap::{HeightmapError, HeightmapGenerator};
+use crate::generation::noise_terrain::NoiseTerrainGenerator;
+use crate::generation::pipeline::{run_terrain_stage, TerrainStage};
+use crate::generation::superflat::SuperflatGenerator;
+use crate::voxel::Voxel;
+use crate::world_settings::WorldSettings;
+
+pub mod caves;
+pub mod debug;
+pub mod decoration;
+pub mod erosion;
+pub mod fractal_noise;
+pub mod heightmap;
+pub mod noise_terrain;
+pub mod ores;
+pub mod pipeline;
+pub mod preview;
+pub mod random;
+pub mod superflat;
+pub mod water;
+
+// Decides what every voxel in the world is. Generators are built once with the world seed and shared between the
+// chunk spawn tasks, so they must be deterministic: a voxel must come out the same whichever chunk asks for it,
+// as a chunk's border is generated from the same function as its neighbour's voxels
+pub trait TerrainGenerator: Send + Sync {
+    fn generate_voxel(&self, global_xyz: &IVec3) -> Voxel;
+
+    // Some(voxel) if every voxel in the chunk is known to be that voxel, which skips generating it voxel by voxel
+    fn uniform_chunk_voxel(&self, _location: &IVec3, _size: &IVec3) -> Option<Voxel> {
+        None
+    }
+
+    // The y of the top ground voxel in a column, water aside, for map previews. None if the generator can't tell
+    // without generating the column
+    fn surface_height(&self, _x: i32, _z: i32) -> Option<i32> {
+        None
+    }
+
+    // The ChunkStatus::Density stage, every voxel of a chunk that isn't uniform. Generators that split their terrain
+    // over the surface and carving stages give the unfinished voxels here, otherwise this is the finished terrain
+    fn generate_density(&self, location: IVec3, size: IVec3) -> ChunkVoxels {
+        let offset = location * size;
+        let voxel_length = (size.x * size.y * size.z) as usize;
+        let mut storage = PaletteStorage::new(voxel_length, Voxel::AIR);
+        for n in 0..voxel_length {
+            let xyz = voxel_index_to_xyz(n as i32, &size);
+            storage.set(n, self.generate_voxel(&(offset + xyz)));
+        }
+        ChunkVoxels::from_palette(storage)
+    }
+
+    // The ChunkStatus::Surface stage, e.g. turning the top of the ground into grass
+    fn generate_surface(&self, _chunk: &mut Chunk) {}
+
+    // The ChunkStatus::Carved stage, e.g. caves. Once it has run the chunk's voxels must match generate_voxel
+    fn carve_chunk(&self, _chunk: &mut Chunk) {}
+
+    // Runs once the chunk's voxels and border are generated, for features bigger than a voxel such as ore veins.
+    // Must write the same voxels into the border as the neighbouring chunk writes into its own voxels
+    fn populate_chunk(&self, _chunk: &mut Chunk) {}
+
+    // The ChunkStatus::Features stage. Places surface decorations such as trees, which may reach into the
+    // neighbouring chunks. Writes the parts inside the chunk, the rest are returned for the ChunkPipeline to pass on
+    fn decorate_chunk(&self, _chunk: &mut Chunk) -> Vec<DecorationWrite> {
+        Vec::new()
+    }
+
+    // Runs the stages that only need the chunk itself, up to ChunkStatus::Carved, for when a chunk is wanted
+    // without its neighbours such as in tests. The ChunkPipeline runs the same stages one at a time
+    fn generate_chunk(&self, location: IVec3, size: IVec3) -> Chunk {
+        let mut chunk = Chunk::filled(size, location, Voxel::AIR);
+        for stage in TerrainStage::ALL {
+            run_terrain_stage(self, &mut chunk, stage);
+        }
+        // Nothing has been meshed yet, so there is nothing to rebuild
+        chunk.dirty = false;
+        chunk
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerrainGeneratorKind {
+    Noise,
+    Superflat,
+    Checkerboard,
+    Sparse,
+    Heightmap,
+}
+
+impl std::str::FromStr for TerrainGeneratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "noise" => Ok(TerrainGeneratorKind::Noise),
+            "superflat" => Ok(TerrainGeneratorKind::Superflat),
+            "checkerboard" => Ok(TerrainGeneratorKind::Checkerboard),
+            "sparse" => Ok(TerrainGeneratorKind::Sparse),
+            "heightmap" => Ok(TerrainGeneratorKind::Heightmap),
+            _ => Err(format!("unknown generator {}", s))
+        }
+    }
+}
+
+// Fails if the generator reads a file that can't be loaded
+pub fn create_terrain_generator(world_settings: &WorldSettings, biome_registry: Arc<BiomeRegistry>) -> Result<Arc<dyn TerrainGenerator>, HeightmapError> {
+    Ok(match world_settings.generator {
+        TerrainGeneratorKind::Noise => {
+            let generator = NoiseTerrainGenerator::new(world_settings.seed, biome_registry);
+            if world_settings.erosion {
+                Arc::new(generator.with_erosion(Erosion::new(world_settings.seed)))
+            } else {
+                Arc::new(generator)
+            }
+        },
+        TerrainGeneratorKind::Superflat => Arc::new(SuperflatGenerator::default()),
+        TerrainGeneratorKind::Checkerboard => Arc::new(CheckerboardGenerator::default()),
+        TerrainGeneratorKind::Sparse => Arc::new(SparseGenerator::new(world_settings.seed)),
+        TerrainGeneratorKind::Heightmap => Arc::new(HeightmapGenerator::load(&world_settings.heightmap)?),
+    })
+}
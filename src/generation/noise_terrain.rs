@@ -0,0 +1,607 @@
+use std::sync::Arc;
+use bevy::math::IVec3;
+use crate::biome::{Biome, BiomeFalloff, BiomeId, BiomeRegistry, BiomeShape, BiomeStrength};
+use crate::block;
+use crate::chunk::Chunk;
+use crate::chunk_storage::{ChunkVoxels, PaletteStorage};
+use crate::chunk_utils::{voxel_index_to_xyz, xyz_to_voxel_index};
+use crate::generation::caves::CaveCarver;
+use crate::generation::decoration::{DecorationWrite, Decorator};
+use crate::generation::erosion::Erosion;
+use crate::generation::ores::OrePlacer;
+use crate::generation::water::WaterFeatures;
+use crate::generation::TerrainGenerator;
+use crate::terrain_noise::TerrainNoise;
+use crate::voxel::{BlockId, Voxel};
+
+// Everything below this height is solid, the base block of the column's biome
+const TERRAIN_MIN_HEIGHT: i32 = 0;
+
+// Climate changes slowly, over hundreds of voxels
+const CLIMATE_SCALE: f64 = 1.0 / 300.0;
+// Moves the humidity field away from temperature so the two are independent
+const HUMIDITY_OFFSET: f64 = 10_000.0;
+
+// Terrain shaped by the biomes in the biome registry, picked from temperature and humidity noise fields on the
+// climate diagram and blended at their edges
+pub struct NoiseTerrainGenerator {
+    terrain_noise: TerrainNoise,
+    biome_registry: Arc<BiomeRegistry>,
+    // Every biome is air at and above this height
+    terrain_max_height: i32,
+    cave_carver: CaveCarver,
+    ore_placer: OrePlacer,
+    decorator: Decorator,
+    water: WaterFeatures,
+    erosion: Option<Erosion>,
+}
+
+impl NoiseTerrainGenerator {
+    pub fn new(seed: i64, biome_registry: Arc<BiomeRegistry>) -> NoiseTerrainGenerator {
+        let water = WaterFeatures::default();
+        NoiseTerrainGenerator {
+            terrain_noise: TerrainNoise::new(seed),
+            terrain_max_height: biome_registry.max_height().max(water.sea_level),
+            biome_registry,
+            cave_carver: CaveCarver::default(),
+            ore_placer: OrePlacer::new(seed),
+            decorator: Decorator::new(seed),
+            water,
+            erosion: None,
+        }
+    }
+
+    // Erosion raises and lowers columns by up to max_change, so the terrain can reach that much higher
+    pub fn with_erosion(mut self, erosion: Erosion) -> NoiseTerrainGenerator {
+        self.terrain_max_height += erosion.max_change;
+        self.erosion = Some(erosion);
+        self
+    }
+
+    pub fn biome_registry(&self) -> &BiomeRegistry {
+        &self.biome_registry
+    }
+
+    // The strongest biome in a column
+    pub fn biome_at(&self, x: i32, z: i32) -> BiomeId {
+        self.get_biome(&IVec3::new(x, 0, z))[0].biome
+    }
+
+    fn get_biome(&self, global_xyz: &IVec3) -> [BiomeStrength; 3] {
+        let (temperature, humidity) = self.climate(global_xyz);
+        self.biome_registry.biome_strengths(temperature, humidity)
+    }
+
+    // Temperature and humidity of a column, both 0 to 1
+    fn climate(&self, global_xyz: &IVec3) -> (f64, f64) {
+        let x = global_xyz.x as f64 * CLIMATE_SCALE;
+        let z = global_xyz.z as f64 * CLIMATE_SCALE;
+        // Simplex noise rarely reaches -1 or 1, stretch it so the edges of the climate diagram are used too
+        let normalise = |noise: f64| ((noise * 1.5 + 1.0) / 2.0).clamp(0.0, 1.0);
+
+        (normalise(self.terrain_noise.eval_2d(x, z)),
+         normalise(self.terrain_noise.eval_2d(x + HUMIDITY_OFFSET, z + HUMIDITY_OFFSET)))
+    }
+
+    // Each biome gives a density for a voxel, positive is solid. Densities are blended by biome strength, so they
+    // change gradually with height for the blend to give a surface part way between the two biomes
+    fn biome_density(&self, biome: &Biome, global_xyz: &IVec3) -> f64 {
+        let range = (biome.max_height - biome.min_height) as f64;
+        let (x, y, z) = (global_xyz.x as f64, global_xyz.y as f64, global_xyz.z as f64);
+
+        match biome.shape {
+            BiomeShape::Density => {
+                let val = biome.noise.sample_3d(&self.terrain_noise, x, y, z);
+
+                // The chance of the voxel being solid, increases the lower y is. Below min_height this is at most 0
+                // so always solid, above max_height it is over 1 so never solid
+                let chance = match biome.falloff {
+                    BiomeFalloff::Linear => (y - biome.min_height as f64) / range,
+                    BiomeFalloff::Log => ((global_xyz.y - biome.min_height).max(1) as f64).log10() / range.log10(),
+                };
+
+                val - chance
+            },
+            BiomeShape::Heightmap => {
+                let val = biome.noise.sample_2d(&self.terrain_noise, x, z);
+                let height = biome.min_height as f64 + val * range;
+                (height - y) / range
+            }
+        }
+    }
+
+    // Everything about a column that doesn't depend on height
+    fn column(&self, x: i32, z: i32) -> TerrainColumn {
+        let mut column = self.uneroded_column(x, z);
+        if let Some(erosion) = &self.erosion {
+            let height_change = erosion.height_change(x, z, |x, z| self.uneroded_surface_height(x, z) as f32);
+            // Rivers and lakes keep their shape rather than silting up
+            column.height_change = (height_change as f64 * (1.0 - column.valley.0)).round() as i32;
+        }
+        column
+    }
+
+    fn uneroded_column(&self, x: i32, z: i32) -> TerrainColumn {
+        TerrainColumn {
+            x,
+            z,
+            biome_strengths: self.get_biome(&IVec3::new(x, 0, z)),
+            valley: self.water.valley(&self.terrain_noise, x as f64, z as f64),
+            height_change: 0,
+        }
+    }
+
+    // The heightmap erosion works on
+    fn uneroded_surface_height(&self, x: i32, z: i32) -> i32 {
+        let column = self.uneroded_column(x, z);
+        (TERRAIN_MIN_HEIGHT..self.terrain_max_height).rev()
+            .find(|y| self.is_ground(&column, *y))
+            .unwrap_or(TERRAIN_MIN_HEIGHT - 1)
+    }
+
+    // Whether the terrain is solid at height y, before caves are carved
+    fn is_ground(&self, column: &TerrainColumn, y: i32) -> bool {
+        if y < TERRAIN_MIN_HEIGHT {
+            return true;
+        }
+        if y >= self.terrain_max_height {
+            return false;
+        }
+
+        // Erosion moves the whole column up or down, keeping any overhangs
+        let y = y - column.height_change;
+        let global_xyz = IVec3::new(column.x, y, column.z);
+        let density: f64 = column.biome_strengths.iter()
+            .filter(|biome_strength| biome_strength.biome_strength > 0.0)
+            .map(|biome_strength| {
+                biome_strength.biome_strength * self.biome_density(self.biome_registry.get(biome_strength.biome), &global_xyz)
+            })
+            .sum();
+
+        self.water.shape_density(density, column.valley, y) > 0.0
+    }
+
+    // The voxel at height y in the column, is_ground gives whether the terrain is solid at a height so a chunk can
+    // look it up once per voxel rather than once per voxel below it
+    fn voxel_in_column<F: Fn(i32) -> bool>(&self, column: &TerrainColumn, y: i32, is_ground: F) -> Voxel {
+        // The strongest biome decides what the ground is made of
+        let biome = self.biome_registry.get(column.biome_strengths[0].biome);
+        if y < TERRAIN_MIN_HEIGHT {
+            return Voxel::new(biome.base_block);
+        }
+
+        if !is_ground(y) {
+            return if self.water.is_underwater(y) { Voxel::new(block::WATER) } else { Voxel::AIR };
+        }
+
+        // By how deep under the surface the voxel is. Caves aren't carved yet so they don't count as surface and their
+        // walls are the base block
+        let depth = (1..=biome.subsurface_depth as i32 + 1)
+            .take_while(|above| is_ground(y + above))
+            .count() as u32;
+        Voxel::new(biome.block_for_depth(depth))
+    }
+
+    // The base block of a chunk under the terrain, if every column in it has the same one
+    fn uniform_base_block(&self, location: &IVec3, size: &IVec3) -> Option<BlockId> {
+        let offset = *location * *size;
+        let base_block = |x: i32, z: i32| self.biome_registry.get(self.get_biome(&IVec3::new(x, 0, z))[0].biome).base_block;
+        let first = base_block(offset.x, offset.z);
+        if self.biome_registry.biomes().iter().all(|biome| biome.base_block == first) {
+            return Some(first);
+        }
+
+        for x in offset.x..offset.x + size.x {
+            for z in offset.z..offset.z + size.z {
+                if base_block(x, z) != first {
+                    return None;
+                }
+            }
+        }
+        Some(first)
+    }
+
+    // Generates the voxel at xyz, needs to also be able to generate voxels for neighbouring chunks
+    fn generate_voxel_at_xyz(&self, global_xyz: &IVec3) -> Voxel {
+        // global xyz means that voxel xyz is from 0..inf
+        // i.e. Chunk 0,0 xyz = 0..32
+        // Chunk 1,1 xyz = 32..64
+        // Chunk 10,10 xyz = 320..352
+        let column = self.column(global_xyz.x, global_xyz.z);
+        self.voxel_in_column(&column, global_xyz.y, |y| self.is_ground(&column, y))
+    }
+
+    fn carve(&self, global_xyz: &IVec3, voxel: Voxel) -> Voxel {
+        // Caves are carved out of the finished terrain, leaving the water
+        let is_ground = voxel != Voxel::AIR && voxel != Voxel::new(block::WATER);
+        if is_ground && self.cave_carver.carves(&self.terrain_noise, global_xyz) {
+            Voxel::AIR
+        } else {
+            voxel
+        }
+    }
+}
+
+struct TerrainColumn {
+    x: i32,
+    z: i32,
+    biome_strengths: [BiomeStrength; 3],
+    valley: (f64, f64),
+    // From erosion, in voxels
+    height_change: i32,
+}
+
+impl TerrainGenerator for NoiseTerrainGenerator {
+    fn generate_voxel(&self, global_xyz: &IVec3) -> Voxel {
+        self.carve(global_xyz, self.generate_voxel_at_xyz(global_xyz))
+    }
+
+    // Stone wherever there is ground, the surface stage then works out what the ground is made of. Goes a column
+    // at a time so the biomes and valleys are only worked out once per column
+    fn generate_density(&self, location: IVec3, size: IVec3) -> ChunkVoxels {
+        let offset = location * size;
+        let mut storage = PaletteStorage::new((size.x * size.y * size.z) as usize, Voxel::AIR);
+
+        for x in 0..size.x {
+            for z in 0..size.z {
+                let column = self.column(offset.x + x, offset.z + z);
+                for y in 0..size.y {
+                    let global_y = offset.y + y;
+                    let voxel = if self.is_ground(&column, global_y) {
+                        Voxel::new(block::STONE)
+                    } else if self.water.is_underwater(global_y) {
+                        Voxel::new(block::WATER)
+                    } else {
+                        Voxel::AIR
+                    };
+                    storage.set(xyz_to_voxel_index(&IVec3::new(x, y, z), &size), voxel);
+                }
+            }
+        }
+
+        ChunkVoxels::from_palette(storage)
+    }
+
+    // Layers the ground by depth. The ground above the chunk is looked up from the noise rather than a neighbour
+    fn generate_surface(&self, chunk: &mut Chunk) {
+        if self.uniform_chunk_voxel(&chunk.location, &chunk.size).is_some() {
+            return;
+        }
+
+        let offset = chunk.location * chunk.size;
+        let size = chunk.size;
+        let lookahead = self.biome_registry.max_subsurface_depth() as i32 + 1;
+
+        for x in 0..size.x {
+            for z in 0..size.z {
+                let column = self.column(offset.x + x, offset.z + z);
+                let above: Vec<bool> = (0..lookahead)
+                    .map(|y| self.is_ground(&column, offset.y + size.y + y))
+                    .collect();
+                let ground: Vec<bool> = (0..size.y)
+                    .map(|y| chunk.get_voxel(&IVec3::new(x, y, z)) == Voxel::new(block::STONE))
+                    .chain(above)
+                    .collect();
+                let is_ground = |y: i32| ground[(y - offset.y) as usize];
+
+                for y in 0..size.y {
+                    if ground[y as usize] {
+                        let voxel = self.voxel_in_column(&column, offset.y + y, is_ground);
+                        chunk.set_voxel(&IVec3::new(x, y, z), voxel);
+                    }
+                }
+            }
+        }
+    }
+
+    fn carve_chunk(&self, chunk: &mut Chunk) {
+        if self.uniform_chunk_voxel(&chunk.location, &chunk.size).is_some() {
+            return;
+        }
+
+        let offset = chunk.location * chunk.size;
+        for n in 0..chunk.voxels.len() {
+            let xyz = voxel_index_to_xyz(n as i32, &chunk.size);
+            let voxel = chunk.get_voxel(&xyz);
+            let carved = self.carve(&(offset + xyz), voxel);
+            if carved != voxel {
+                chunk.set_voxel(&xyz, carved);
+            }
+        }
+    }
+
+    // Ignores caves, which would otherwise show as pits wherever one reaches the surface
+    fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        let column = self.column(x, z);
+        (TERRAIN_MIN_HEIGHT..self.terrain_max_height).rev().find(|y| self.is_ground(&column, *y))
+    }
+
+    fn populate_chunk(&self, chunk: &mut Chunk) {
+        self.ore_placer.place_ores(chunk);
+    }
+
+    fn decorate_chunk(&self, chunk: &mut Chunk) -> Vec<DecorationWrite> {
+        self.decorator.decorate(chunk)
+    }
+
+    fn uniform_chunk_voxel(&self, location: &IVec3, size: &IVec3) -> Option<Voxel> {
+        let bottom = location.y * size.y;
+        if bottom >= self.terrain_max_height {
+            // Nothing generates this high, skip evaluating the noise entirely
+            Some(Voxel::AIR)
+        } else if bottom + size.y <= TERRAIN_MIN_HEIGHT.min(self.cave_carver.min_height + 1) {
+            self.uniform_base_block(location, size).map(Voxel::new)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::biome::{BiomeDefinition, BiomeRegistry};
+    use crate::block;
+    use crate::block::BlockRegistry;
+    use crate::chunk::tests::{assert_load_order_independent, assert_seamless};
+    use crate::chunk_mesh::{generate_mesh, generate_water_mesh};
+    use crate::generation::erosion::Erosion;
+    use crate::generation::noise_terrain::NoiseTerrainGenerator;
+    use crate::generation::TerrainGenerator;
+    use crate::voxel::Voxel;
+    use crate::world_settings::WorldSettings;
+    use bevy::math::IVec3;
+
+    fn test_generator() -> NoiseTerrainGenerator {
+        let biome_registry = Arc::new(BiomeRegistry::builtin(&BlockRegistry::default()));
+        NoiseTerrainGenerator::new(WorldSettings::default().seed, biome_registry)
+    }
+
+    // A world of a single heightmap biome, with extra fields such as block names in toml
+    fn single_biome_generator(min_height: i32, max_height: i32, toml: &str) -> NoiseTerrainGenerator {
+        let definition: BiomeDefinition = toml::from_str(&format!(r#"
+            name = "only"
+            weight = 1.0
+            temperature = 0.5
+            humidity = 0.5
+            shape = "heightmap"
+            min_height = {}
+            max_height = {}
+            noise_scale = 0.02
+            {}
+        "#, min_height, max_height, toml)).unwrap();
+        let biome_registry = BiomeRegistry::from_definitions(vec![definition], &BlockRegistry::default()).unwrap();
+        NoiseTerrainGenerator::new(WorldSettings::default().seed, Arc::new(biome_registry))
+    }
+
+    #[test]
+    fn base_block_continues_below_zero_test() {
+        let generator = single_biome_generator(10, 20, r#"
+            top_block = "sand"
+            subsurface_block = "sand"
+            subsurface_depth = 2
+            base_block = "dirt"
+        "#);
+        let dirt = Voxel::new(block::DIRT);
+        assert_eq!(generator.generate_voxel(&IVec3::new(3, 5, 3)), dirt);
+        assert_eq!(generator.generate_voxel(&IVec3::new(3, -5, 3)), dirt);
+        assert_eq!(generator.generate_voxel(&IVec3::new(3, -500, 3)), dirt);
+
+        let chunk = generator.generate_chunk(IVec3::new(0, -20, 0), IVec3::new(32, 32, 32));
+        assert_eq!(chunk.voxels.uniform_voxel(), Some(dirt));
+    }
+
+    #[test]
+    fn terrain_can_be_taller_than_64_test() {
+        let generator = single_biome_generator(100, 160, r#"
+            top_block = "grass"
+            subsurface_block = "dirt"
+            subsurface_depth = 3
+        "#);
+        for x in 0..32 {
+            assert!(surface_height(&generator, x, x) >= 100);
+        }
+        // Stacked chunks well above 64 are generated like any other
+        let chunk = generator.generate_chunk(IVec3::new(0, 3, 0), IVec3::new(32, 32, 32));
+        assert!(!chunk.is_uniform());
+        assert!(generate_mesh(&chunk, &BlockRegistry::default()).is_some());
+    }
+
+    #[test]
+    fn chunk_above_terrain_is_uniform_air_test() {
+        let chunk = test_generator().generate_chunk(IVec3::new(3, 2, -2), IVec3::new(32, 32, 32));
+        assert_eq!(chunk.voxels.uniform_voxel(), Some(Voxel::AIR));
+        assert!(generate_mesh(&chunk, &BlockRegistry::default()).is_none());
+    }
+
+    #[test]
+    fn chunk_below_terrain_is_uniform_and_hidden_test() {
+        let chunk = test_generator().generate_chunk(IVec3::new(0, -2, 0), IVec3::new(32, 32, 32));
+        assert!(chunk.is_uniform());
+        // Surrounded by more stone on every side, so nothing is visible
+        assert!(generate_mesh(&chunk, &BlockRegistry::default()).is_none());
+    }
+
+    // The highest solid voxel in a column, under any water
+    fn surface_height(generator: &NoiseTerrainGenerator, x: i32, z: i32) -> i32 {
+        (0..generator.terrain_max_height).rev()
+            .find(|y| {
+                let voxel = generator.generate_voxel_at_xyz(&IVec3::new(x, *y, z));
+                voxel != Voxel::AIR && voxel != Voxel::new(block::WATER)
+            })
+            .unwrap_or(-1)
+    }
+
+    fn dominant_biome(generator: &NoiseTerrainGenerator, x: i32, z: i32) -> usize {
+        generator.get_biome(&IVec3::new(x, 0, z))[0].biome.0
+    }
+
+    #[test]
+    fn no_cliffs_at_biome_edges_test() {
+        const MAX_STEP: i32 = 6;
+        let generator = test_generator();
+
+        let mut edges_crossed = 0;
+        for z in [0, 250, 500] {
+            for x in -1500..1500 {
+                if dominant_biome(&generator, x, z) != dominant_biome(&generator, x + 1, z) {
+                    edges_crossed += 1;
+                    let step = surface_height(&generator, x, z) - surface_height(&generator, x + 1, z);
+                    assert!(step.abs() <= MAX_STEP, "{} voxel cliff at {} {}", step, x, z);
+                }
+            }
+        }
+        assert!(edges_crossed > 0);
+    }
+
+    #[test]
+    fn desert_and_snow_columns_never_touch_test() {
+        let generator = test_generator();
+        let desert = generator.biome_registry.id_by_name("desert").unwrap().0;
+        let snow = generator.biome_registry.id_by_name("snow").unwrap().0;
+
+        let mut seen = [false, false];
+        for z in (-3000..3000).step_by(10) {
+            for x in (-3000..3000).step_by(10) {
+                let here = dominant_biome(&generator, x, z);
+                seen[0] |= here == desert;
+                seen[1] |= here == snow;
+                for (nx, nz) in [(x + 10, z), (x, z + 10)] {
+                    let there = dominant_biome(&generator, nx, nz);
+                    assert!(!(here == desert && there == snow || here == snow && there == desert),
+                            "desert next to snow at {} {}", x, z);
+                }
+            }
+        }
+        assert!(seen[0] && seen[1]);
+    }
+
+    #[test]
+    fn caves_are_seamless_across_chunks_test() {
+        let generator = test_generator();
+        let size = IVec3::new(32, 32, 32);
+        let chunks = assert_load_order_independent(&[IVec3::new(0, -1, 0), IVec3::new(1, -1, 0), IVec3::new(0, 0, 0)],
+                                                    |locations| locations.iter().map(|location| generator.generate_chunk(*location, size)).collect());
+        let chunk = chunks.iter().find(|chunk| chunk.location == IVec3::new(0, -1, 0)).unwrap();
+
+        // Everything below 0 is stone before carving, so any air is a cave
+        let carved = (0..32 * 32 * 32)
+            .filter(|n| chunk.get_voxel(&IVec3::new(n % 32, n / 32 % 32, n / 1024)) == Voxel::AIR)
+            .count();
+        assert!(carved > 0);
+    }
+
+    #[test]
+    fn ground_is_layered_by_depth_test() {
+        let generator = test_generator();
+        let size = IVec3::new(32, 32, 32);
+        let top_blocks: Vec<_> = generator.biome_registry.biomes().iter().map(|biome| biome.top_block).collect();
+        let max_depth = generator.biome_registry.max_subsurface_depth() as i32;
+
+        let mut surfaces = 0;
+        for location in [IVec3::new(0, 0, 0), IVec3::new(3, 0, -2)] {
+            let chunk = generator.generate_chunk(location, size);
+            let offset = location * size;
+            for x in 0..32 {
+                for z in 0..32 {
+                    for y in 0..32 {
+                        // Generating a column at a time gives the same voxels as one at a time, ores aside
+                        let voxel = chunk.get_voxel(&IVec3::new(x, y, z));
+                        let expected = generator.generate_voxel(&(offset + IVec3::new(x, y, z)));
+                        assert!(voxel == expected || expected == Voxel::new(block::STONE), "{:?} at {} {} {}", voxel, x, y, z);
+                    }
+
+                    let surface = surface_height(&generator, offset.x + x, offset.z + z);
+                    if surface < 0 || surface >= 32 - max_depth {
+                        continue;
+                    }
+                    surfaces += 1;
+                    let top = generator.generate_voxel_at_xyz(&IVec3::new(offset.x + x, surface, offset.z + z));
+                    assert!(top_blocks.contains(&top.block_id));
+                    // Deep enough under the surface that every biome has reached its base, unless under an overhang
+                    let column: Vec<_> = (1..=max_depth + 1)
+                        .map(|depth| generator.generate_voxel_at_xyz(&IVec3::new(offset.x + x, surface - depth, offset.z + z)))
+                        .collect();
+                    if column.iter().all(|voxel| *voxel != Voxel::AIR && *voxel != Voxel::new(block::WATER)) {
+                        assert_eq!(column[max_depth as usize], Voxel::new(block::STONE));
+                    }
+                }
+            }
+        }
+        assert!(surfaces > 0);
+    }
+
+    #[test]
+    fn eroded_chunks_are_seamless_test() {
+        let mut erosion = Erosion::new(42).with_regions(32, 8).unwrap();
+        erosion.droplets_per_region = 1500;
+        let eroded = test_generator().with_erosion(erosion);
+        let uneroded = test_generator();
+
+        let size = IVec3::new(32, 32, 32);
+        let chunk = eroded.generate_chunk(IVec3::new(0, 0, 0), size);
+        let east = eroded.generate_chunk(IVec3::new(1, 0, 0), size);
+        assert_seamless(&chunk, &east);
+
+        let changed = (0..64)
+            .filter(|x| eroded.surface_height(*x, 5) != uneroded.surface_height(*x, 5))
+            .count();
+        assert!(changed > 0);
+    }
+
+    #[test]
+    fn sea_fills_low_ground_test() {
+        let generator = test_generator();
+        let sea_level = generator.water.sea_level;
+        let water = Voxel::new(block::WATER);
+
+        let mut wet_columns = 0;
+        for x in (-2000..2000).step_by(40) {
+            for z in (-2000..2000).step_by(40) {
+                let surface = surface_height(&generator, x, z);
+                for y in surface + 1..sea_level + 2 {
+                    let expected = if y < sea_level { water } else { Voxel::AIR };
+                    assert_eq!(generator.generate_voxel_at_xyz(&IVec3::new(x, y, z)), expected);
+                }
+                if surface < sea_level - 1 {
+                    wet_columns += 1;
+                }
+            }
+        }
+        assert!(wet_columns > 0);
+    }
+
+    #[test]
+    fn water_is_meshed_separately_test() {
+        let generator = test_generator();
+        let block_registry = BlockRegistry::default();
+
+        // Find a chunk holding the sea surface
+        let chunk = (-20..20)
+            .map(|x| generator.generate_chunk(IVec3::new(x, 0, 0), IVec3::new(16, 16, 16)))
+            .find(|chunk| chunk.get_voxel(&IVec3::new(8, 11, 8)) == Voxel::new(block::WATER))
+            .unwrap();
+        assert!(generate_water_mesh(&chunk, &block_registry).is_some());
+        assert!(!block_registry.is_solid(&chunk.get_voxel(&IVec3::new(8, 11, 8))));
+    }
+
+    #[test]
+    fn generate_voxel_at_xyz_test() {
+        let generator = test_generator();
+        let actual = generator.generate_voxel_at_xyz(&IVec3::new(0, 0, 0));
+        assert_eq!(generator.generate_voxel_at_xyz(&IVec3::new(0, 0, 0)), actual);
+
+        let generator2 = test_generator();
+        assert_eq!(generator2.generate_voxel_at_xyz(&IVec3::new(0, 0, 0)), actual);
+
+        for x in -3..67 {
+            for y in -3..67 {
+                for z in -3..67 {
+                    assert_eq!(
+                        generator.generate_voxel_at_xyz(&IVec3::new(x, y, z)),
+                        generator2.generate_voxel_at_xyz(&IVec3::new(x, y, z)));
+                }
+            }
+        }
+    }
+}
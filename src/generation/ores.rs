@@ -0,0 +1,184 @@
+use bevy::math::IVec3;
+use crate::block;
+use crate::chunk::Chunk;
+use crate::generation::random::{hash_position, hash_position_f64, split_mix};
+use crate::voxel::{BlockId, Voxel};
+
+// Share of the voxels inside a vein's radius that are ore, the rest are left as the host so veins look lumpy
+const VEIN_FILL: f64 = 0.7;
+
+#[derive(Clone, Copy, Debug)]
+pub struct OreVein {
+    pub block_id: BlockId,
+    // Ore only replaces this block, so it never appears in air, caves or other ores
+    pub host_block: BlockId,
+    // In voxels, must be smaller than the chunk so a vein reaches at most one chunk over
+    pub radius: i32,
+    pub veins_per_chunk: u32,
+    // Vein centres are between min_height and max_height
+    pub min_height: i32,
+    pub max_height: i32,
+}
+
+// Places blobs of ore in generated chunks. Each chunk's veins are picked from the world seed and chunk location, and
+// a chunk also places the parts of its neighbours' veins that reach into it, so the result doesn't depend on which
+// chunks are generated first
+pub struct OrePlacer {
+    seed: i64,
+    pub veins: Vec<OreVein>,
+}
+
+impl OrePlacer {
+    pub fn new(seed: i64) -> OrePlacer {
+        OrePlacer {
+            seed,
+            veins: vec![
+                OreVein { block_id: block::DIRT, host_block: block::STONE, radius: 4, veins_per_chunk: 3, min_height: -28, max_height: 40 },
+                OreVein { block_id: block::COAL_ORE, host_block: block::STONE, radius: 3, veins_per_chunk: 8, min_height: -28, max_height: 40 },
+                OreVein { block_id: block::IRON_ORE, host_block: block::STONE, radius: 2, veins_per_chunk: 4, min_height: -28, max_height: 16 },
+            ],
+        }
+    }
+
+    pub fn place_ores(&self, chunk: &mut Chunk) {
+        let offset = chunk.location * chunk.size;
+
+        for (vein_index, vein) in self.veins.iter().enumerate() {
+            // Skip veins that can't reach this chunk or its border
+            if vein.max_height + vein.radius < offset.y - 1 || vein.min_height - vein.radius > offset.y + chunk.size.y {
+                continue;
+            }
+
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let cell = chunk.location + IVec3::new(x, y, z);
+                        for centre in self.vein_centres(vein_index, vein, cell, chunk.size) {
+                            self.place_vein(chunk, vein, vein_index, centre - offset);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Global positions of the veins belonging to the chunk at cell
+    fn vein_centres(&self, vein_index: usize, vein: &OreVein, cell: IVec3, size: IVec3) -> Vec<IVec3> {
+        let cell_offset = cell * size;
+        let mut hash = hash_position(self.vein_seed(vein_index), &cell);
+
+        let mut centres = Vec::new();
+        for _ in 0..vein.veins_per_chunk {
+            let mut next = |len: i32| {
+                hash = split_mix(hash);
+                (hash % len as u64) as i32
+            };
+            let centre = cell_offset + IVec3::new(next(size.x), next(size.y), next(size.z));
+            if centre.y >= vein.min_height && centre.y <= vein.max_height {
+                centres.push(centre);
+            }
+        }
+        centres
+    }
+
+    // local_centre is relative to the chunk, and can be outside it
+    fn place_vein(&self, chunk: &mut Chunk, vein: &OreVein, vein_index: usize, local_centre: IVec3) {
+        let offset = chunk.location * chunk.size;
+        let radius = vein.radius;
+        let min = (local_centre - radius).max(IVec3::splat(-1));
+        let max = (local_centre + radius).min(chunk.size);
+        let fill_seed = self.vein_seed(vein_index) ^ 0x5eed;
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let local_xyz = IVec3::new(x, y, z);
+                    let from_centre = local_xyz - local_centre;
+                    if from_centre.dot(from_centre) > radius * radius {
+                        continue;
+                    }
+                    // Keyed on the global position so both chunks sharing a border voxel agree
+                    if hash_position_f64(fill_seed, &(offset + local_xyz)) >= VEIN_FILL {
+                        continue;
+                    }
+                    let is_host = (chunk.contains(&local_xyz) || chunk.border.contains(&local_xyz))
+                        && chunk.get_voxel_or_border(&local_xyz) == Voxel::new(vein.host_block);
+                    if is_host {
+                        chunk.set_voxel_or_border(&local_xyz, Voxel::new(vein.block_id));
+                    }
+                }
+            }
+        }
+    }
+
+    fn vein_seed(&self, vein_index: usize) -> i64 {
+        split_mix(self.seed as u64 ^ (vein_index as u64 + 1).wrapping_mul(0x9e37_79b9)) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block;
+    use crate::chunk::Chunk;
+    use crate::chunk::tests::assert_load_order_independent;
+    use crate::generation::ores::OrePlacer;
+    use crate::voxel::Voxel;
+    use bevy::math::IVec3;
+
+    const SIDE: i32 = 16;
+
+    fn count_ores(chunk: &Chunk) -> usize {
+        (0..SIDE * SIDE * SIDE)
+            .map(|n| chunk.get_voxel(&IVec3::new(n % SIDE, n / SIDE % SIDE, n / (SIDE * SIDE))))
+            .filter(|voxel| *voxel != Voxel::new(block::STONE) && *voxel != Voxel::AIR)
+            .count()
+    }
+
+    #[test]
+    fn ores_are_deterministic_and_seamless_test() {
+        let ore_placer = OrePlacer::new(42);
+        let generate = |location: IVec3| {
+            let mut chunk = Chunk::filled(IVec3::splat(SIDE), location, Voxel::new(block::STONE));
+            ore_placer.place_ores(&mut chunk);
+            chunk
+        };
+
+        // Generated again, or in the other order, the same ores come out
+        let chunks = assert_load_order_independent(&[IVec3::new(0, 0, 0), IVec3::new(1, 0, 0)],
+                                                    |locations| locations.iter().map(|location| generate(*location)).collect());
+        let chunk = &chunks[0];
+        assert!(count_ores(chunk) > 0);
+
+        assert_ne!(count_ores(chunk), count_ores(&{
+            let mut other_seed = Chunk::filled(IVec3::splat(SIDE), IVec3::ZERO, Voxel::new(block::STONE));
+            OrePlacer::new(7).place_ores(&mut other_seed);
+            other_seed
+        }));
+    }
+
+    #[test]
+    fn ores_never_replace_air_test() {
+        let ore_placer = OrePlacer::new(42);
+
+        let mut air = Chunk::filled(IVec3::splat(SIDE), IVec3::ZERO, Voxel::AIR);
+        ore_placer.place_ores(&mut air);
+        assert_eq!(air.voxels.uniform_voxel(), Some(Voxel::AIR));
+
+        // Top half air, bottom half stone
+        let mut half = Chunk::filled(IVec3::splat(SIDE), IVec3::ZERO, Voxel::new(block::STONE));
+        for n in 0..SIDE * SIDE * SIDE {
+            let xyz = IVec3::new(n % SIDE, n / SIDE % SIDE, n / (SIDE * SIDE));
+            if xyz.y >= SIDE / 2 {
+                half.set_voxel(&xyz, Voxel::AIR);
+            }
+        }
+        ore_placer.place_ores(&mut half);
+        assert!(count_ores(&half) > 0);
+        for n in 0..SIDE * SIDE * SIDE {
+            let xyz = IVec3::new(n % SIDE, n / SIDE % SIDE, n / (SIDE * SIDE));
+            if xyz.y >= SIDE / 2 {
+                assert_eq!(half.get_voxel(&xyz), Voxel::AIR);
+            }
+        }
+    }
+}
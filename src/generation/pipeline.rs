@@ -0,0 +1,598 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use bevy::math::IVec3;
+use bevy::prelude::Mesh;
+use crate::block::BlockRegistry;
+use crate::chunk::Chunk;
+use crate::chunk_border::ChunkBorder;
+use crate::chunk_mesh::{generate_mesh, generate_water_mesh};
+use crate::chunk_storage::ChunkVoxels;
+use crate::chunk_utils::world_to_chunk_location;
+use crate::generation::decoration::{DecorationWrite, merge_decoration};
+use crate::generation::TerrainGenerator;
+use crate::voxel::Voxel;
+
+// How far through generation a chunk is, each status is reached by running the stage of the same name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkStatus {
+    Empty,
+    // Where the ground is, as stone
+    Density,
+    // What the ground is made of, e.g. grass over dirt
+    Surface,
+    // Caves carved out, the border filled in and ores placed. The chunk now matches TerrainGenerator::generate_voxel
+    Carved,
+    // Trees and boulders, which can reach into the neighbours
+    Features,
+    Meshed,
+}
+
+impl ChunkStatus {
+    pub const STAGES: [ChunkStatus; 5] = [
+        ChunkStatus::Density,
+        ChunkStatus::Surface,
+        ChunkStatus::Carved,
+        ChunkStatus::Features,
+        ChunkStatus::Meshed,
+    ];
+
+    pub fn next(self) -> Option<ChunkStatus> {
+        ChunkStatus::STAGES.iter().copied().find(|stage| *stage > self)
+    }
+
+    // The status every chunk within the returned number of chunks must have reached before this stage can run.
+    // Decorations are written into the neighbours, so they need their terrain. The mesh needs the border to be final,
+    // which is once every chunk that can decorate into it or its neighbours has been decorated
+    pub fn neighbour_requirement(self) -> Option<(ChunkStatus, i32)> {
+        match self {
+            ChunkStatus::Features => Some((ChunkStatus::Carved, 1)),
+            ChunkStatus::Meshed => Some((ChunkStatus::Features, 2)),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChunkStatus::Empty => "empty",
+            ChunkStatus::Density => "density",
+            ChunkStatus::Surface => "surface",
+            ChunkStatus::Carved => "carving",
+            ChunkStatus::Features => "features",
+            ChunkStatus::Meshed => "meshing",
+        }
+    }
+}
+
+// The stages that only need the chunk itself, which TerrainGenerator::generate_chunk runs without the pipeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainStage {
+    Density,
+    Surface,
+    Carved,
+}
+
+impl TerrainStage {
+    pub const ALL: [TerrainStage; 3] = [TerrainStage::Density, TerrainStage::Surface, TerrainStage::Carved];
+
+    pub fn status(self) -> ChunkStatus {
+        match self {
+            TerrainStage::Density => ChunkStatus::Density,
+            TerrainStage::Surface => ChunkStatus::Surface,
+            TerrainStage::Carved => ChunkStatus::Carved,
+        }
+    }
+}
+
+pub fn run_terrain_stage<G: TerrainGenerator + ?Sized>(generator: &G, chunk: &mut Chunk, stage: TerrainStage) {
+    match stage {
+        TerrainStage::Density => {
+            chunk.voxels = match generator.uniform_chunk_voxel(&chunk.location, &chunk.size) {
+                Some(voxel) => ChunkVoxels::uniform(chunk.voxels.len(), voxel),
+                None => generator.generate_density(chunk.location, chunk.size)
+            };
+        },
+        TerrainStage::Surface => generator.generate_surface(chunk),
+        TerrainStage::Carved => {
+            generator.carve_chunk(chunk);
+            let offset = chunk.location * chunk.size;
+            chunk.border = ChunkBorder::generate(chunk.size, |local_xyz| generator.generate_voxel(&(offset + *local_xyz)));
+            generator.populate_chunk(chunk);
+        },
+    }
+    chunk.status = stage.status();
+}
+
+// A stage to run on a chunk, which can be done on any thread
+pub struct StageJob {
+    pub chunk_location: IVec3,
+    pub stage: ChunkStatus,
+    // None for the density stage, which makes the chunk
+    pub chunk: Option<Chunk>,
+}
+
+pub enum StageOutput {
+    Nothing,
+    // Parts of decorations outside the chunk, and those on its faces which land in the neighbours' borders
+    Decorations(Vec<DecorationWrite>),
+    Meshes { mesh: Option<Mesh>, water_mesh: Option<Mesh> },
+}
+
+pub struct FinishedStage {
+    pub chunk: Chunk,
+    pub stage: ChunkStatus,
+    pub output: StageOutput,
+    pub elapsed: Duration,
+}
+
+impl StageJob {
+    pub fn run(self, generator: &dyn TerrainGenerator, block_registry: &BlockRegistry, chunk_size: IVec3) -> FinishedStage {
+        let start = Instant::now();
+        let mut chunk = self.chunk.unwrap_or_else(|| Chunk::filled(chunk_size, self.chunk_location, Voxel::AIR));
+
+        let output = match self.stage {
+            // Never handed out, a job is always for a stage after the chunk's status
+            ChunkStatus::Empty => StageOutput::Nothing,
+            ChunkStatus::Density => {
+                run_terrain_stage(generator, &mut chunk, TerrainStage::Density);
+                StageOutput::Nothing
+            },
+            ChunkStatus::Surface => {
+                run_terrain_stage(generator, &mut chunk, TerrainStage::Surface);
+                StageOutput::Nothing
+            },
+            ChunkStatus::Carved => {
+                run_terrain_stage(generator, &mut chunk, TerrainStage::Carved);
+                StageOutput::Nothing
+            },
+            ChunkStatus::Features => {
+                let before = chunk.clone();
+                let mut writes = generator.decorate_chunk(&mut chunk);
+                writes.extend(face_changes(&before, &chunk));
+                StageOutput::Decorations(writes)
+            },
+            ChunkStatus::Meshed => StageOutput::Meshes {
+                mesh: generate_mesh(&chunk, block_registry),
+                water_mesh: generate_water_mesh(&chunk, block_registry),
+            },
+        };
+        chunk.status = self.stage;
+        if self.stage == ChunkStatus::Meshed {
+            chunk.dirty = false;
+        }
+
+        FinishedStage { chunk, stage: self.stage, output, elapsed: start.elapsed() }
+    }
+}
+
+struct PipelineChunk {
+    // None while a stage is running on it, or once it has been meshed and handed over
+    chunk: Option<Chunk>,
+    status: ChunkStatus,
+    // The status it has been requested to reach
+    target: ChunkStatus,
+    running: bool,
+    // The writes its decorations made, kept for neighbours that are generated again after being forgotten
+    decorations: Vec<DecorationWrite>,
+    meshes: Option<(Option<Mesh>, Option<Mesh>)>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTiming {
+    pub chunks: u32,
+    pub total: Duration,
+}
+
+// Takes chunks through the generation stages in order. A chunk is requested up to a status, and its neighbours are
+// requested as far as its stages need them. Stages are handed out as jobs that can run on other threads, and a stage
+// only runs once the neighbours have reached its neighbour_requirement. Decorations reaching into other chunks are
+// passed on here, and only land in a chunk once it has been decorated itself, so the result doesn't depend on the
+// order chunks finish in
+pub struct ChunkPipeline {
+    chunk_size: IVec3,
+    chunks: HashMap<IVec3, PipelineChunk>,
+    // Decoration writes for chunks that were running a stage when the writes came in
+    pending_writes: HashMap<IVec3, Vec<DecorationWrite>>,
+    timings: HashMap<ChunkStatus, StageTiming>,
+}
+
+impl ChunkPipeline {
+    pub fn new(chunk_size: IVec3) -> ChunkPipeline {
+        ChunkPipeline {
+            chunk_size,
+            chunks: HashMap::new(),
+            pending_writes: HashMap::new(),
+            timings: HashMap::new(),
+        }
+    }
+
+    pub fn status(&self, chunk_location: &IVec3) -> ChunkStatus {
+        self.chunks.get(chunk_location).map_or(ChunkStatus::Empty, |entry| entry.status)
+    }
+
+    pub fn request(&mut self, chunk_location: IVec3, target: ChunkStatus) {
+        let entry = self.chunks.entry(chunk_location).or_insert(PipelineChunk {
+            chunk: None,
+            status: ChunkStatus::Empty,
+            target: ChunkStatus::Empty,
+            running: false,
+            decorations: Vec::new(),
+            meshes: None,
+        });
+        if entry.target >= target {
+            return;
+        }
+        entry.target = target;
+
+        // The stages up to target need the neighbours this far along, which is never as far as target
+        let requirements: Vec<(ChunkStatus, i32)> = ChunkStatus::STAGES.iter().filter(|stage| **stage <= target)
+            .filter_map(|stage| stage.neighbour_requirement())
+            .collect();
+        for (requirement, radius) in requirements {
+            for neighbour in neighbours(chunk_location, radius) {
+                self.request(neighbour, requirement);
+            }
+        }
+    }
+
+    // Takes every chunk whose next stage can run now, nearest to from first
+    pub fn ready_jobs(&mut self, from: IVec3) -> Vec<StageJob> {
+        // Neighbours forgotten since they were requested are requested again
+        let waiting: Vec<(IVec3, ChunkStatus, i32)> = self.chunks.iter()
+            .filter(|(_, entry)| !entry.running && entry.status < entry.target)
+            .filter_map(|(chunk_location, entry)| entry.status.next().unwrap().neighbour_requirement()
+                .map(|(requirement, radius)| (*chunk_location, requirement, radius)))
+            .filter(|(chunk_location, _, radius)| neighbours(*chunk_location, *radius).iter()
+                .any(|neighbour| !self.chunks.contains_key(neighbour)))
+            .collect();
+        for (chunk_location, requirement, radius) in waiting {
+            for neighbour in neighbours(chunk_location, radius) {
+                self.request(neighbour, requirement);
+            }
+        }
+
+        let mut ready: Vec<(IVec3, ChunkStatus)> = self.chunks.iter()
+            .filter(|(_, entry)| !entry.running && entry.status < entry.target)
+            .map(|(chunk_location, entry)| (*chunk_location, entry.status.next().unwrap()))
+            .filter(|(chunk_location, stage)| match stage.neighbour_requirement() {
+                Some((requirement, radius)) => neighbours(*chunk_location, radius).iter()
+                    .all(|neighbour| self.status(neighbour) >= requirement),
+                None => true
+            })
+            .collect();
+        ready.sort_by_key(|(chunk_location, stage)| ((*chunk_location - from).abs().max_element(), *stage, chunk_location.to_array()));
+
+        ready.into_iter()
+            .map(|(chunk_location, stage)| {
+                let entry = self.chunks.get_mut(&chunk_location).unwrap();
+                entry.running = true;
+                StageJob { chunk_location, stage, chunk: entry.chunk.take() }
+            })
+            .collect()
+    }
+
+    pub fn finish(&mut self, finished: FinishedStage) {
+        let timing = self.timings.entry(finished.stage).or_default();
+        timing.chunks += 1;
+        timing.total += finished.elapsed;
+
+        let chunk_location = finished.chunk.location;
+        let entry = match self.chunks.get_mut(&chunk_location) {
+            // Forgotten while the stage ran, and maybe requested again since
+            Some(entry) if entry.running && entry.status.next() == Some(finished.stage) => entry,
+            _ => return
+        };
+        entry.status = finished.stage;
+        entry.running = false;
+        entry.chunk = Some(finished.chunk);
+
+        match finished.output {
+            StageOutput::Nothing => {},
+            StageOutput::Decorations(writes) => {
+                for write in writes.iter() {
+                    self.write_decoration(write);
+                }
+                self.chunks.get_mut(&chunk_location).unwrap().decorations = writes;
+                self.gather_decorations(&chunk_location);
+            },
+            StageOutput::Meshes { mesh, water_mesh } => entry.meshes = Some((mesh, water_mesh)),
+        }
+
+        if let Some(writes) = self.pending_writes.remove(&chunk_location) {
+            for write in writes.iter() {
+                self.apply_decoration(&chunk_location, write);
+            }
+        }
+    }
+
+    // Runs every stage until nothing more can be done, on this thread
+    pub fn run(&mut self, generator: &dyn TerrainGenerator, block_registry: &BlockRegistry) {
+        loop {
+            let jobs = self.ready_jobs(IVec3::ZERO);
+            if jobs.is_empty() {
+                return;
+            }
+            for job in jobs {
+                let finished = job.run(generator, block_registry, self.chunk_size);
+                self.finish(finished);
+            }
+        }
+    }
+
+    // Meshed chunks are handed over with their meshes, after which only their status is kept
+    pub fn take_meshed(&mut self) -> Vec<(Chunk, Option<Mesh>, Option<Mesh>)> {
+        let mut meshed = Vec::new();
+        for entry in self.chunks.values_mut() {
+            if entry.status == ChunkStatus::Meshed && !entry.running {
+                if let (Some(chunk), Some((mesh, water_mesh))) = (entry.chunk.take(), entry.meshes.take()) {
+                    meshed.push((chunk, mesh, water_mesh));
+                }
+            }
+        }
+        meshed
+    }
+
+    // A chunk that has reached its status, without taking it out of the pipeline
+    pub fn get_chunk(&self, chunk_location: &IVec3) -> Option<&Chunk> {
+        self.chunks.get(chunk_location).and_then(|entry| entry.chunk.as_ref())
+    }
+
+    // Drops the chunks keep returns false for, and anything waiting to be written into them. Stages still running on
+    // them are thrown away when they finish
+    pub fn forget<F: Fn(&IVec3) -> bool>(&mut self, keep: F) {
+        self.chunks.retain(|chunk_location, _| keep(chunk_location));
+        self.pending_writes.retain(|chunk_location, _| keep(chunk_location));
+    }
+
+    // Drops a single chunk, e.g. once it is despawned, so it goes through the stages again if it is requested
+    pub fn remove(&mut self, chunk_location: &IVec3) {
+        self.chunks.remove(chunk_location);
+        self.pending_writes.remove(chunk_location);
+    }
+
+    pub fn timings(&self) -> Vec<(ChunkStatus, StageTiming)> {
+        ChunkStatus::STAGES.iter()
+            .map(|stage| (*stage, self.timings.get(stage).copied().unwrap_or_default()))
+            .collect()
+    }
+
+    // Average time per chunk of each stage, for profiling
+    pub fn timings_report(&self) -> String {
+        self.timings().iter()
+            .map(|(stage, timing)| {
+                let average = timing.total.as_secs_f64() * 1000.0 / timing.chunks.max(1) as f64;
+                format!("{} {} chunks {:.2}ms", stage.name(), timing.chunks, average)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // A write lands in the chunk containing it and in the border of the neighbours next to it. Chunks that haven't
+    // been decorated yet gather it themselves once they are, see gather_decorations
+    fn write_decoration(&mut self, write: &DecorationWrite) {
+        let containing_chunk = world_to_chunk_location(&write.world_xyz, &self.chunk_size);
+        for chunk_location in neighbours(containing_chunk, 1).into_iter().chain([containing_chunk]) {
+            let entry = match self.chunks.get(&chunk_location) {
+                Some(entry) => entry,
+                None => continue
+            };
+            // Undecorated chunks gather the write themselves, and meshed chunks already have it as every neighbour was
+            // decorated before they were meshed
+            if entry.status < ChunkStatus::Features || entry.status == ChunkStatus::Meshed {
+                continue;
+            }
+            if entry.running {
+                self.pending_writes.entry(chunk_location).or_default().push(*write);
+            } else {
+                self.apply_decoration(&chunk_location, write);
+            }
+        }
+    }
+
+    // Applies the writes of every decorated neighbour to a chunk that has just been decorated
+    fn gather_decorations(&mut self, chunk_location: &IVec3) {
+        let writes: Vec<DecorationWrite> = neighbours(*chunk_location, 1).iter()
+            .filter_map(|neighbour| self.chunks.get(neighbour))
+            .filter(|entry| entry.status >= ChunkStatus::Features)
+            .flat_map(|entry| entry.decorations.iter().copied())
+            .collect();
+        for write in writes.iter() {
+            self.apply_decoration(chunk_location, write);
+        }
+    }
+
+    fn apply_decoration(&mut self, chunk_location: &IVec3, write: &DecorationWrite) {
+        let local_xyz = write.world_xyz - *chunk_location * self.chunk_size;
+        let chunk = match self.chunks.get_mut(chunk_location).and_then(|entry| entry.chunk.as_mut()) {
+            Some(chunk) => chunk,
+            None => return
+        };
+        // Corners and edges past the chunk aren't in the border
+        if chunk.contains(&local_xyz) || chunk.border.contains(&local_xyz) {
+            let current = chunk.get_voxel_or_border(&local_xyz);
+            chunk.set_voxel_or_border(&local_xyz, merge_decoration(current, write.voxel));
+        }
+    }
+}
+
+// The voxels on the chunk's faces that decorating changed, which the neighbours have in their border
+fn face_changes(before: &Chunk, after: &Chunk) -> Vec<DecorationWrite> {
+    let offset = after.location * after.size;
+    let mut changes = Vec::new();
+    for x in 0..after.size.x {
+        for y in 0..after.size.y {
+            for z in 0..after.size.z {
+                let local_xyz = IVec3::new(x, y, z);
+                if after.is_on_border(&local_xyz) && after.get_voxel(&local_xyz) != before.get_voxel(&local_xyz) {
+                    changes.push(DecorationWrite { world_xyz: offset + local_xyz, voxel: after.get_voxel(&local_xyz) });
+                }
+            }
+        }
+    }
+    changes
+}
+
+// Every chunk within radius chunks of chunk_location on each axis, apart from itself
+fn neighbours(chunk_location: IVec3, radius: i32) -> Vec<IVec3> {
+    let side = (2 * radius + 1) as usize;
+    let mut neighbours = Vec::with_capacity(side * side * side - 1);
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                if x != 0 || y != 0 || z != 0 {
+                    neighbours.push(chunk_location + IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+    neighbours
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::biome::BiomeRegistry;
+    use crate::block::BlockRegistry;
+    use crate::generation::decoration::{DecorationWrite, Decorator};
+    use crate::generation::noise_terrain::NoiseTerrainGenerator;
+    use crate::generation::pipeline::{ChunkPipeline, ChunkStatus};
+    use crate::generation::superflat::SuperflatGenerator;
+    use crate::generation::TerrainGenerator;
+    use crate::chunk::Chunk;
+    use crate::chunk::tests::assert_load_order_independent;
+    use crate::voxel::Voxel;
+    use bevy::math::IVec3;
+
+    const SIDE: i32 = 16;
+
+    // Superflat with plenty of trees, which reach out of the top of the y = 0 chunks and across their sides
+    struct ForestGenerator {
+        superflat: SuperflatGenerator,
+        decorator: Decorator,
+    }
+
+    impl TerrainGenerator for ForestGenerator {
+        fn generate_voxel(&self, global_xyz: &IVec3) -> Voxel {
+            self.superflat.generate_voxel(global_xyz)
+        }
+
+        fn uniform_chunk_voxel(&self, location: &IVec3, size: &IVec3) -> Option<Voxel> {
+            self.superflat.uniform_chunk_voxel(location, size)
+        }
+
+        fn decorate_chunk(&self, chunk: &mut Chunk) -> Vec<DecorationWrite> {
+            self.decorator.decorate(chunk)
+        }
+    }
+
+    fn forest() -> ForestGenerator {
+        let mut decorator = Decorator::new(42);
+        decorator.trees_per_chunk = 12;
+        decorator.boulders_per_chunk = 3;
+        ForestGenerator { superflat: SuperflatGenerator::default(), decorator }
+    }
+
+    fn meshed_in_order(chunk_locations: &[IVec3]) -> Vec<Chunk> {
+        let generator = forest();
+        let block_registry = BlockRegistry::default();
+        let mut pipeline = ChunkPipeline::new(IVec3::splat(SIDE));
+        for chunk_location in chunk_locations {
+            pipeline.request(*chunk_location, ChunkStatus::Meshed);
+            pipeline.run(&generator, &block_registry);
+        }
+
+        pipeline.take_meshed().into_iter().map(|(chunk, _, _)| chunk).collect()
+    }
+
+    #[test]
+    fn stages_wait_for_neighbours_test() {
+        let generator = forest();
+        let block_registry = BlockRegistry::default();
+        let mut pipeline = ChunkPipeline::new(IVec3::splat(SIDE));
+        pipeline.request(IVec3::ZERO, ChunkStatus::Meshed);
+
+        // Every stage of a chunk runs after the one before, and after its neighbours reached what it needs
+        loop {
+            let jobs = pipeline.ready_jobs(IVec3::ZERO);
+            if jobs.is_empty() {
+                break;
+            }
+            for job in jobs {
+                let before = pipeline.status(&job.chunk_location);
+                assert_eq!(before.next(), Some(job.stage));
+                if let Some((requirement, radius)) = job.stage.neighbour_requirement() {
+                    for neighbour in super::neighbours(job.chunk_location, radius) {
+                        assert!(pipeline.status(&neighbour) >= requirement);
+                    }
+                }
+                let finished = job.run(&generator, &block_registry, IVec3::splat(SIDE));
+                pipeline.finish(finished);
+            }
+        }
+
+        assert_eq!(pipeline.status(&IVec3::ZERO), ChunkStatus::Meshed);
+        assert_eq!(pipeline.status(&IVec3::new(1, 0, -1)), ChunkStatus::Features);
+        assert_eq!(pipeline.status(&IVec3::new(2, 0, 0)), ChunkStatus::Features);
+        assert_eq!(pipeline.status(&IVec3::new(0, 3, 0)), ChunkStatus::Carved);
+        assert_eq!(pipeline.status(&IVec3::new(4, 0, 0)), ChunkStatus::Empty);
+        assert_eq!(pipeline.take_meshed().len(), 1);
+        assert!(pipeline.timings().iter().all(|(_, timing)| timing.chunks > 0));
+    }
+
+    #[test]
+    fn decorations_do_not_depend_on_order_test() {
+        let mut chunk_locations = Vec::new();
+        for x in -1..=1 {
+            for y in 0..=1 {
+                for z in -1..=1 {
+                    chunk_locations.push(IVec3::new(x, y, z));
+                }
+            }
+        }
+
+        // Borders match the neighbouring chunks' voxels too, trees included
+        let chunks = assert_load_order_independent(&chunk_locations, meshed_in_order);
+
+        // Trees grow out of the top of the y = 0 chunks into the ones above
+        assert!(chunks.iter().any(|chunk| chunk.location.y == 1 && !chunk.is_uniform()));
+    }
+
+    #[test]
+    fn staged_noise_terrain_matches_generate_voxel_test() {
+        let biome_registry = Arc::new(BiomeRegistry::builtin(&BlockRegistry::default()));
+        let generator = NoiseTerrainGenerator::new(42, biome_registry);
+        let size = IVec3::splat(32);
+
+        for location in [IVec3::new(0, 0, 0), IVec3::new(-2, -1, 3)] {
+            let chunk = generator.generate_chunk(location, size);
+            assert_eq!(chunk.status, ChunkStatus::Carved);
+            for n in 0..32 * 32 * 32 {
+                let xyz = IVec3::new(n % 32, n / 32 % 32, n / 1024);
+                let expected = generator.generate_voxel(&(location * size + xyz));
+                // Ores replace stone after carving
+                let voxel = chunk.get_voxel(&xyz);
+                assert!(voxel == expected || expected == Voxel::new(crate::block::STONE), "{:?} {:?} at {}", voxel, expected, xyz);
+            }
+        }
+    }
+
+    #[test]
+    fn forgotten_chunks_are_dropped_test() {
+        let generator = forest();
+        let block_registry = BlockRegistry::default();
+        let mut pipeline = ChunkPipeline::new(IVec3::splat(SIDE));
+        pipeline.request(IVec3::ZERO, ChunkStatus::Features);
+        let jobs = pipeline.ready_jobs(IVec3::ZERO);
+
+        pipeline.forget(|chunk_location| chunk_location.x <= 0);
+        for job in jobs {
+            let finished = job.run(&generator, &block_registry, IVec3::splat(SIDE));
+            pipeline.finish(finished);
+        }
+        assert_eq!(pipeline.status(&IVec3::new(1, 0, 0)), ChunkStatus::Empty);
+        assert!(pipeline.get_chunk(&IVec3::new(1, 0, 0)).is_none());
+
+        // The missing neighbours come back before the chunk is decorated
+        pipeline.run(&generator, &block_registry);
+        assert_eq!(pipeline.status(&IVec3::ZERO), ChunkStatus::Features);
+    }
+}
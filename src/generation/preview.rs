@@ -0,0 +1,101 @@
+use bevy::math::IVec3;
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use crate::block;
+use crate::generation::noise_terrain::NoiseTerrainGenerator;
+use crate::generation::TerrainGenerator;
+use crate::voxel::Voxel;
+
+// A rectangle of world columns seen from above, one pixel for every step columns. x is along the image and z down it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PreviewArea {
+    pub from_x: i32,
+    pub from_z: i32,
+    pub width: u32,
+    pub depth: u32,
+    pub step: i32,
+}
+
+impl PreviewArea {
+    // Centred on a column
+    pub fn around(x: i32, z: i32, width: u32, depth: u32, step: i32) -> PreviewArea {
+        PreviewArea {
+            from_x: x - width as i32 * step / 2,
+            from_z: z - depth as i32 * step / 2,
+            width,
+            depth,
+            step,
+        }
+    }
+
+    fn column(&self, pixel_x: u32, pixel_z: u32) -> (i32, i32) {
+        (self.from_x + pixel_x as i32 * self.step, self.from_z + pixel_z as i32 * self.step)
+    }
+}
+
+// Each column coloured by its strongest biome's map_color
+pub fn biome_preview(generator: &NoiseTerrainGenerator, area: &PreviewArea) -> RgbImage {
+    RgbImage::from_fn(area.width, area.depth, |pixel_x, pixel_z| {
+        let (x, z) = area.column(pixel_x, pixel_z);
+        Rgb(generator.biome_registry().get(generator.biome_at(x, z)).map_color)
+    })
+}
+
+// The height of the ground in each column, black at min_height and white at max_height. Generators that can't give
+// a column's height have it searched for between the two
+pub fn height_preview(generator: &dyn TerrainGenerator, area: &PreviewArea, min_height: i32, max_height: i32) -> GrayImage {
+    GrayImage::from_fn(area.width, area.depth, |pixel_x, pixel_z| {
+        let (x, z) = area.column(pixel_x, pixel_z);
+        let height = generator.surface_height(x, z).unwrap_or_else(|| {
+            (min_height..=max_height).rev()
+                .find(|y| {
+                    let voxel = generator.generate_voxel(&IVec3::new(x, *y, z));
+                    voxel != Voxel::AIR && voxel != Voxel::new(block::WATER)
+                })
+                .unwrap_or(min_height)
+        });
+        let brightness = (height - min_height) as f64 / (max_height - min_height) as f64;
+        Luma([(brightness.clamp(0.0, 1.0) * 255.0).round() as u8])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::biome::BiomeRegistry;
+    use crate::block::BlockRegistry;
+    use crate::generation::debug::CheckerboardGenerator;
+    use crate::generation::noise_terrain::NoiseTerrainGenerator;
+    use crate::generation::preview::{biome_preview, height_preview, PreviewArea};
+    use crate::generation::superflat::SuperflatGenerator;
+
+    #[test]
+    fn biome_preview_test() {
+        let biome_registry = Arc::new(BiomeRegistry::builtin(&BlockRegistry::default()));
+        let generator = NoiseTerrainGenerator::new(42, biome_registry.clone());
+        let area = PreviewArea::around(0, 0, 64, 48, 32);
+
+        let image = biome_preview(&generator, &area);
+        assert_eq!(image.dimensions(), (64, 48));
+        // Every pixel is one of the biomes, and an area this big has more than one
+        let colors: Vec<[u8; 3]> = biome_registry.biomes().iter().map(|biome| biome.map_color).collect();
+        assert!(image.pixels().all(|pixel| colors.contains(&pixel.0)));
+        assert!(image.pixels().any(|pixel| *pixel != image.pixels().next().copied().unwrap()));
+
+        let heights = height_preview(&generator, &area, 0, 64);
+        assert!(heights.pixels().any(|pixel| pixel.0[0] > 0));
+    }
+
+    #[test]
+    fn height_preview_test() {
+        let area = PreviewArea { from_x: -4, from_z: 0, width: 8, depth: 8, step: 1 };
+
+        // Grass at y 11
+        let superflat = height_preview(&SuperflatGenerator::default(), &area, 0, 22);
+        assert!(superflat.pixels().all(|pixel| pixel.0[0] == 128));
+
+        // Searched for, the top is at y 1 or y 0 in alternate columns
+        let checkerboard = height_preview(&CheckerboardGenerator::default(), &area, -1, 1);
+        assert_eq!(checkerboard.get_pixel(0, 0).0[0], 128);
+        assert_eq!(checkerboard.get_pixel(1, 0).0[0], 255);
+    }
+}
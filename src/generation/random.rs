@@ -0,0 +1,39 @@
+use bevy::math::IVec3;
+
+// Deterministic per position randomness, so a generator gives the same answer for a voxel whichever chunk or
+// thread asks for it. Uses the SplitMix64 finaliser
+pub fn hash_position(seed: i64, xyz: &IVec3) -> u64 {
+    let mut hash = seed as u64;
+    for coordinate in [xyz.x, xyz.y, xyz.z] {
+        hash = split_mix(hash ^ (coordinate as u32 as u64));
+    }
+    hash
+}
+
+// 0.0..1.0
+pub fn hash_position_f64(seed: i64, xyz: &IVec3) -> f64 {
+    (hash_position(seed, xyz) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+pub fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::random::{hash_position, hash_position_f64};
+    use bevy::math::IVec3;
+
+    #[test]
+    fn hash_position_test() {
+        assert_eq!(hash_position(1, &IVec3::new(3, -4, 5)), hash_position(1, &IVec3::new(3, -4, 5)));
+        assert_ne!(hash_position(1, &IVec3::new(3, -4, 5)), hash_position(2, &IVec3::new(3, -4, 5)));
+        assert_ne!(hash_position(1, &IVec3::new(3, -4, 5)), hash_position(1, &IVec3::new(5, -4, 3)));
+
+        let mean = (0..1000).map(|x| hash_position_f64(7, &IVec3::new(x, 0, 0))).sum::<f64>() / 1000.0;
+        assert!((mean - 0.5).abs() < 0.05);
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::math::IVec3;
+use crate::block;
+use crate::generation::TerrainGenerator;
+use crate::voxel::{BlockId, Voxel};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SuperflatLayer {
+    pub block_id: BlockId,
+    pub thickness: i32,
+}
+
+// Flat horizontal layers, listed from the bottom up starting at base_height. Below the layers is stone and above
+// them is air
+pub struct SuperflatGenerator {
+    pub base_height: i32,
+    pub layers: Vec<SuperflatLayer>,
+}
+
+impl Default for SuperflatGenerator {
+    fn default() -> Self {
+        SuperflatGenerator {
+            base_height: 0,
+            layers: vec![
+                SuperflatLayer { block_id: block::STONE, thickness: 8 },
+                SuperflatLayer { block_id: block::DIRT, thickness: 3 },
+                SuperflatLayer { block_id: block::GRASS, thickness: 1 },
+            ],
+        }
+    }
+}
+
+impl SuperflatGenerator {
+    pub fn surface_height(&self) -> i32 {
+        self.base_height + self.layers.iter().map(|layer| layer.thickness).sum::<i32>()
+    }
+}
+
+impl TerrainGenerator for SuperflatGenerator {
+    fn generate_voxel(&self, global_xyz: &IVec3) -> Voxel {
+        if global_xyz.y < self.base_height {
+            return Voxel::new(block::STONE);
+        }
+
+        let mut layer_top = self.base_height;
+        for layer in self.layers.iter() {
+            layer_top += layer.thickness;
+            if global_xyz.y < layer_top {
+                return Voxel::new(layer.block_id);
+            }
+        }
+
+        Voxel::AIR
+    }
+
+    fn surface_height(&self, _x: i32, _z: i32) -> Option<i32> {
+        Some(self.surface_height() - 1)
+    }
+
+    fn uniform_chunk_voxel(&self, location: &IVec3, size: &IVec3) -> Option<Voxel> {
+        let bottom = location.y * size.y;
+        if bottom >= self.surface_height() {
+            Some(Voxel::AIR)
+        } else if bottom + size.y <= self.base_height {
+            Some(Voxel::new(block::STONE))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block;
+    use crate::generation::superflat::SuperflatGenerator;
+    use crate::generation::TerrainGenerator;
+    use crate::voxel::Voxel;
+    use bevy::math::IVec3;
+
+    #[test]
+    fn superflat_layers_test() {
+        let generator = SuperflatGenerator::default();
+        assert_eq!(generator.generate_voxel(&IVec3::new(5, -20, 9)), Voxel::new(block::STONE));
+        assert_eq!(generator.generate_voxel(&IVec3::new(5, 7, 9)), Voxel::new(block::STONE));
+        assert_eq!(generator.generate_voxel(&IVec3::new(-5, 8, 9)), Voxel::new(block::DIRT));
+        assert_eq!(generator.generate_voxel(&IVec3::new(5, 11, -9)), Voxel::new(block::GRASS));
+        assert_eq!(generator.generate_voxel(&IVec3::new(5, 12, 9)), Voxel::AIR);
+
+        let chunk = generator.generate_chunk(IVec3::new(2, 0, -1), IVec3::new(16, 16, 16));
+        assert_eq!(chunk.get_voxel(&IVec3::new(3, 11, 3)), Voxel::new(block::GRASS));
+        assert_eq!(chunk.get_voxel_or_border(&IVec3::new(3, -1, 3)), Voxel::new(block::STONE));
+
+        let chunk = generator.generate_chunk(IVec3::new(2, 1, -1), IVec3::new(16, 16, 16));
+        assert_eq!(chunk.voxels.uniform_voxel(), Some(Voxel::AIR));
+        assert_eq!(chunk.get_voxel_or_border(&IVec3::new(3, -1, 3)), Voxel::AIR);
+    }
+}
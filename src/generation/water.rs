@@ -0,0 +1,98 @@
+use crate::generation::fractal_noise::FractalNoise;
+use crate::terrain_noise::TerrainNoise;
+
+// How quickly the ground rises either side of a river or lake bed, in voxels per unit of density
+const VALLEY_SLOPE: f64 = 16.0;
+
+// The sea, and the rivers and lakes that cut down to it. Rivers follow the middle of a noise, where it crosses 0.5,
+// so they wind and rarely end. Lakes are where a second noise peaks. Both lower the terrain to a bed below sea
+// level, and the sea fills every air voxel below sea level that the terrain leaves
+pub struct WaterFeatures {
+    pub sea_level: i32,
+    pub river_noise: FractalNoise,
+    // How far from the middle of the river noise the river reaches
+    pub river_width: f64,
+    // Below sea level, in the middle of the river
+    pub river_depth: f64,
+    pub lake_noise: FractalNoise,
+    // Lakes are where the lake noise is above this
+    pub lake_threshold: f64,
+    pub lake_depth: f64,
+}
+
+impl Default for WaterFeatures {
+    fn default() -> Self {
+        WaterFeatures {
+            sea_level: 12,
+            river_noise: FractalNoise::fbm(0.003, 2).with_seed_offset(50_000.0),
+            river_width: 0.02,
+            river_depth: 4.0,
+            lake_noise: FractalNoise::fbm(0.008, 1).with_seed_offset(60_000.0),
+            lake_threshold: 0.8,
+            lake_depth: 6.0,
+        }
+    }
+}
+
+impl WaterFeatures {
+    pub fn is_underwater(&self, y: i32) -> bool {
+        y < self.sea_level
+    }
+
+    // How strongly a column is pulled down into a river or lake, 0 to 1, and how far below sea level its bed is
+    pub fn valley(&self, terrain_noise: &TerrainNoise, x: f64, z: f64) -> (f64, f64) {
+        let river_noise = self.river_noise.sample_2d(terrain_noise, x, z);
+        let river = (1.0 - (river_noise - 0.5).abs() / self.river_width).max(0.0);
+
+        let lake_noise = self.lake_noise.sample_2d(terrain_noise, x, z);
+        // Rises quickly past the threshold, so lakes are mostly flat bottomed with a short shore
+        let lake = ((lake_noise - self.lake_threshold) / (1.0 - self.lake_threshold) * 4.0).clamp(0.0, 1.0);
+
+        (river.max(lake), (river * self.river_depth).max(lake * self.lake_depth))
+    }
+
+    // Blends a terrain density towards the valley bed. Only ever removes ground, so a river never builds a wall
+    pub fn shape_density(&self, density: f64, valley: (f64, f64), y: i32) -> f64 {
+        let (strength, depth) = valley;
+        if strength <= 0.0 {
+            return density;
+        }
+
+        let bed = self.sea_level as f64 - depth;
+        let valley_density = (bed - y as f64) / VALLEY_SLOPE;
+        // Smoothstep, so the banks ease in rather than starting at an angle
+        let strength = strength * strength * (3.0 - 2.0 * strength);
+        density.min(density + (valley_density - density) * strength)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::water::WaterFeatures;
+    use crate::terrain_noise::TerrainNoise;
+
+    #[test]
+    fn valleys_only_lower_terrain_test() {
+        let water = WaterFeatures::default();
+        let terrain_noise = TerrainNoise::new(42);
+
+        let mut river_columns = 0;
+        for i in 0..4000 {
+            let valley = water.valley(&terrain_noise, i as f64 * 3.0, i as f64 * -1.5);
+            assert!((0.0..=1.0).contains(&valley.0));
+            if valley.0 >= 1.0 {
+                river_columns += 1;
+            }
+            for y in -10..60 {
+                for density in [-0.5, 0.0, 0.3, 1.0] {
+                    assert!(water.shape_density(density, valley, y) <= density);
+                }
+            }
+        }
+        assert!(river_columns > 0);
+
+        // In the middle of a valley the ground is below sea level, so it fills with water
+        let bed = water.shape_density(1.0, (1.0, water.river_depth), water.sea_level - 1);
+        assert!(bed <= 0.0);
+    }
+}
@@ -0,0 +1,20 @@
+pub mod chunk_utils;
+pub mod chunk_vertexes;
+pub mod chunk_manager;
+pub mod voxel;
+pub mod block;
+pub mod chunk_mesh;
+pub mod systems;
+pub mod biome;
+pub mod flycamerafork;
+pub mod chunk_spawner;
+pub mod chunk;
+pub mod chunk_storage;
+pub mod chunk_border;
+pub mod voxel_world;
+pub mod terrain_noise;
+pub mod generation;
+pub mod world_settings;
+
+#[macro_use]
+extern crate exec_time;
@@ -1,30 +1,25 @@
-mod chunk_utils;
-mod chunk_vertexes;
-mod chunk_manager;
-mod voxel;
-mod chunk_mesh;
-mod systems;
-mod biome;
-mod flycamerafork;
-mod chunk_spawner;
-mod chunk;
-
-#[macro_use]
-extern crate exec_time;
-
+use std::sync::Arc;
 use bevy::prelude::*;
 use bevy::render::camera::PerspectiveProjection;
-use flycamerafork::fly_camera::{FlyCamera, FlyCameraPlugin};
-use crate::chunk::{Chunk};
-use crate::chunk_manager::{ChunkManager, get_chunk_containing_position};
-use crate::chunk_mesh::generate_mesh;
+use rust_bevy_game::biome::BiomeRegistry;
+use rust_bevy_game::block::BlockRegistry;
+use rust_bevy_game::chunk_manager::{ChunkManager, ChunkMaterials};
+use rust_bevy_game::chunk_spawner;
+use rust_bevy_game::flycamerafork::fly_camera::{FlyCamera, FlyCameraPlugin};
+use rust_bevy_game::generation::{create_terrain_generator, TerrainGenerator};
+use rust_bevy_game::systems;
+use rust_bevy_game::voxel_world::VoxelWorld;
+use rust_bevy_game::world_settings::WorldSettings;
 
 fn init(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
+    world_settings: Res<WorldSettings>,
+    block_registry: Res<Arc<BlockRegistry>>,
+    terrain_generator: Res<Arc<dyn TerrainGenerator>>,
 ) {
-    let start_transform = Transform::from_translation(Vec3::new(32.0, 64.0, 32.0));
+    let start_transform = Transform::from_translation(world_settings.spawn_point);
     let fly_camera = FlyCamera {
         sensitivity: 10.0,
         friction: 5.0,
@@ -47,23 +42,63 @@ fn init(
         })
         .insert(fly_camera);
 
-    let chunk_size = IVec3::new(32, 64, 32);
-    let center_chunk_location = get_chunk_containing_position(&start_transform.translation, &chunk_size);
-    let atlas = materials.add(StandardMaterial {
-        base_color_texture: Some(asset_server.load("atlas.png").clone()),
-        unlit: true,
-        ..Default::default()
-    });
+    let atlas_texture = asset_server.load("atlas.png");
+    let chunk_materials = ChunkMaterials {
+        terrain: materials.add(StandardMaterial {
+            base_color_texture: Some(atlas_texture.clone()),
+            unlit: true,
+            ..Default::default()
+        }),
+        // The water tile in the atlas is partly transparent
+        water: materials.add(StandardMaterial {
+            base_color_texture: Some(atlas_texture),
+            unlit: true,
+            alpha_mode: AlphaMode::Blend,
+            double_sided: true,
+            cull_mode: None,
+            ..Default::default()
+        }),
+    };
 
-    commands.spawn().insert(ChunkManager::new(
-        chunk_size,
-        center_chunk_location,
-        atlas
-    ));
+    commands.insert_resource(VoxelWorld::new(ChunkManager::new(
+        &world_settings,
+        chunk_materials,
+        block_registry.clone(),
+        terrain_generator.clone()
+    )));
 }
 
 fn main() {
+    let world_settings = match WorldSettings::from_args(std::env::args().skip(1)) {
+        Ok(world_settings) => world_settings,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    println!("Generating world with seed {}", world_settings.seed);
+
+    let block_registry = Arc::new(BlockRegistry::default());
+    let biome_registry = match BiomeRegistry::load_dir(&world_settings.biomes_dir, &block_registry) {
+        Ok(biome_registry) => Arc::new(biome_registry),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let terrain_generator = match create_terrain_generator(&world_settings, biome_registry.clone()) {
+        Ok(terrain_generator) => terrain_generator,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
     App::new()
+        .insert_resource(world_settings)
+        .insert_resource(block_registry)
+        .insert_resource(biome_registry)
+        .insert_resource(terrain_generator)
         .insert_resource(ClearColor(Color::rgb(0.2, 0.2, 0.8)))
         .insert_resource(Msaa { samples: 4 })
         .insert_resource(bevy::render::settings::WgpuFeatures::all_native_mask())
@@ -72,6 +107,7 @@ fn main() {
         .add_startup_system(init)
         .add_plugin(FlyCameraPlugin)
         .add_system(systems::mouse_toggle::mouse_toggle)
+        .add_system(systems::stage_timings::log_stage_timings)
         .add_plugin(chunk_spawner::chunk_spawner_plugin::ChunkSpawnerPlugin)
         .run();
 }
\ No newline at end of file
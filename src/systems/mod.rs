@@ -1 +1,2 @@
-pub mod mouse_toggle;
\ No newline at end of file
+pub mod mouse_toggle;
+pub mod stage_timings;
\ No newline at end of file
@@ -0,0 +1,12 @@
+use bevy::prelude::{info, Input, KeyCode, Res};
+use crate::voxel_world::VoxelWorld;
+
+// Press "P" to log how long each chunk generation stage has taken per chunk
+pub fn log_stage_timings(
+    input: Res<Input<KeyCode>>,
+    voxel_world: Res<VoxelWorld>,
+) {
+    if input.just_pressed(KeyCode::P) {
+        info!("Chunk stage timings: {}", voxel_world.chunk_manager.pipeline.timings_report());
+    }
+}
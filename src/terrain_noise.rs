@@ -0,0 +1,46 @@
+use opensimplex_noise_rs::OpenSimplexNoise;
+
+// The noise sources every chunk is generated from. Built once from the world seed and shared between spawn tasks
+// through an Arc, so the permutation tables exist once rather than once per chunk
+pub struct TerrainNoise {
+    seed: i64,
+    simplex: OpenSimplexNoise,
+}
+
+impl TerrainNoise {
+    pub fn new(seed: i64) -> TerrainNoise {
+        TerrainNoise {
+            seed,
+            simplex: OpenSimplexNoise::new(Some(seed)),
+        }
+    }
+
+    pub fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    pub fn eval_2d(&self, x: f64, y: f64) -> f64 {
+        self.simplex.eval_2d(x, y)
+    }
+
+    pub fn eval_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.simplex.eval_3d(x, y, z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::terrain_noise::TerrainNoise;
+
+    #[test]
+    fn shared_noise_matches_across_threads_test() {
+        let terrain_noise = Arc::new(TerrainNoise::new(42));
+        let expected = terrain_noise.eval_3d(1.5, -2.25, 3.0);
+
+        let thread_noise = terrain_noise.clone();
+        let actual = std::thread::spawn(move || thread_noise.eval_3d(1.5, -2.25, 3.0)).join().unwrap();
+        assert_eq!(actual, expected);
+        assert_eq!(TerrainNoise::new(42).eval_3d(1.5, -2.25, 3.0), expected);
+    }
+}
@@ -1,10 +1,18 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+use crate::block;
+
+// Index into the BlockRegistry
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockId(pub u16);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Voxel {
-    pub solid: bool
+    pub block_id: BlockId
 }
 
 impl Voxel {
-    pub fn new(solid: bool) -> Voxel {
-        Voxel { solid }
+    pub const AIR: Voxel = Voxel { block_id: block::AIR };
+
+    pub fn new(block_id: BlockId) -> Voxel {
+        Voxel { block_id }
     }
-}
\ No newline at end of file
+}
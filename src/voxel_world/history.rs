@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+use bevy::math::IVec3;
+use crate::voxel::Voxel;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoxelChange {
+    pub world_xyz: IVec3,
+    pub old: Voxel,
+    pub new: Voxel,
+}
+
+// A group of changes that are undone and redone together, e.g. one fill_box
+#[derive(Clone, Debug, Default)]
+pub struct EditTransaction {
+    pub changes: Vec<VoxelChange>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EditHistoryConfig {
+    // How many transactions can be undone and redone, together
+    pub max_transactions: usize,
+    // Total voxel changes kept across every transaction, the oldest transactions are dropped first. This is what
+    // bounds memory, a single huge fill is dropped entirely rather than kept partially
+    pub max_changes: usize,
+}
+
+impl Default for EditHistoryConfig {
+    fn default() -> Self {
+        EditHistoryConfig {
+            max_transactions: 100,
+            max_changes: 1_000_000,
+        }
+    }
+}
+
+pub struct EditHistory {
+    config: EditHistoryConfig,
+    undo_stack: VecDeque<EditTransaction>,
+    redo_stack: Vec<EditTransaction>,
+    open_transaction: Option<EditTransaction>,
+    // Depth of nested begin_transaction calls, the transaction is only pushed when the outermost one ends
+    open_depth: usize,
+    change_count: usize,
+}
+
+impl EditHistory {
+    pub fn new(config: EditHistoryConfig) -> EditHistory {
+        EditHistory {
+            config,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            open_transaction: None,
+            open_depth: 0,
+            change_count: 0,
+        }
+    }
+
+    pub fn config(&self) -> EditHistoryConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: EditHistoryConfig) {
+        self.config = config;
+        self.enforce_limits();
+    }
+
+    pub fn begin_transaction(&mut self) {
+        if self.open_depth == 0 {
+            self.open_transaction = Some(EditTransaction::default());
+        }
+        self.open_depth += 1;
+    }
+
+    pub fn end_transaction(&mut self) {
+        if self.open_depth == 0 {
+            return;
+        }
+
+        self.open_depth -= 1;
+        if self.open_depth == 0 {
+            if let Some(transaction) = self.open_transaction.take() {
+                self.push(transaction);
+            }
+        }
+    }
+
+    // Changes made outside of a transaction are undone one at a time
+    pub fn record(&mut self, change: VoxelChange) {
+        match &mut self.open_transaction {
+            Some(transaction) => transaction.changes.push(change),
+            None => self.push(EditTransaction { changes: vec![change] })
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn peek_undo(&self) -> Option<&EditTransaction> {
+        self.undo_stack.back()
+    }
+
+    pub fn peek_redo(&self) -> Option<&EditTransaction> {
+        self.redo_stack.last()
+    }
+
+    // Moves the latest transaction onto the redo stack, the caller applies its old values
+    pub fn pop_undo(&mut self) -> Option<EditTransaction> {
+        let transaction = self.undo_stack.pop_back()?;
+        self.redo_stack.push(transaction.clone());
+        Some(transaction)
+    }
+
+    // Moves the latest undone transaction back onto the undo stack, the caller applies its new values
+    pub fn pop_redo(&mut self) -> Option<EditTransaction> {
+        let transaction = self.redo_stack.pop()?;
+        self.undo_stack.push_back(transaction.clone());
+        Some(transaction)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.change_count = 0;
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        size_of::<EditHistory>() + self.change_count * size_of::<VoxelChange>()
+            + (self.undo_stack.len() + self.redo_stack.len()) * size_of::<EditTransaction>()
+    }
+
+    fn push(&mut self, transaction: EditTransaction) {
+        if transaction.changes.is_empty() {
+            return;
+        }
+
+        // A new edit invalidates anything that was undone
+        for undone in self.redo_stack.drain(..) {
+            self.change_count -= undone.changes.len();
+        }
+
+        self.change_count += transaction.changes.len();
+        self.undo_stack.push_back(transaction);
+        self.enforce_limits();
+    }
+
+    // Drops the oldest undo first, then the redo furthest from the current state
+    fn enforce_limits(&mut self) {
+        while self.undo_stack.len() + self.redo_stack.len() > self.config.max_transactions
+            || self.change_count > self.config.max_changes {
+            let dropped = match self.undo_stack.pop_front() {
+                Some(oldest) => oldest,
+                None if !self.redo_stack.is_empty() => self.redo_stack.remove(0),
+                None => break
+            };
+            self.change_count -= dropped.changes.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block;
+    use crate::voxel::Voxel;
+    use crate::voxel_world::history::EditHistoryConfig;
+    use crate::voxel_world::tests::test_world;
+    use bevy::math::IVec3;
+
+    #[test]
+    fn undo_redo_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0)]);
+        let stone = Voxel::new(block::STONE);
+        let dirt = Voxel::new(block::DIRT);
+
+        world.set_voxel(&IVec3::new(1, 1, 1), stone).unwrap();
+        world.fill_box(&IVec3::new(0, 0, 0), &IVec3::new(2, 2, 2), dirt);
+        world.take_dirty_chunks();
+
+        // Undoes the whole box in one go
+        assert_eq!(world.undo(), Ok(true));
+        assert_eq!(world.get_voxel(&IVec3::new(0, 0, 0)), Ok(Voxel::AIR));
+        assert_eq!(world.get_voxel(&IVec3::new(1, 1, 1)), Ok(stone));
+        assert_eq!(world.take_dirty_chunks(), vec![IVec3::new(0, 0, 0)]);
+
+        assert_eq!(world.undo(), Ok(true));
+        assert_eq!(world.get_voxel(&IVec3::new(1, 1, 1)), Ok(Voxel::AIR));
+        assert_eq!(world.undo(), Ok(false));
+
+        assert_eq!(world.redo(), Ok(true));
+        assert_eq!(world.redo(), Ok(true));
+        assert_eq!(world.get_voxel(&IVec3::new(1, 1, 1)), Ok(dirt));
+        assert_eq!(world.redo(), Ok(false));
+
+        // A new edit drops the redo stack
+        world.undo().unwrap();
+        world.set_voxel(&IVec3::new(5, 5, 5), stone).unwrap();
+        assert_eq!(world.redo(), Ok(false));
+    }
+
+    #[test]
+    fn grouped_transaction_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0)]);
+        let stone = Voxel::new(block::STONE);
+
+        world.begin_edit_transaction();
+        world.set_voxel(&IVec3::new(1, 1, 1), stone).unwrap();
+        world.carve_sphere(&IVec3::new(1, 1, 1), 0.0);
+        world.set_voxel(&IVec3::new(2, 2, 2), stone).unwrap();
+        world.end_edit_transaction();
+
+        assert_eq!(world.undo(), Ok(true));
+        assert_eq!(world.get_voxel(&IVec3::new(2, 2, 2)), Ok(Voxel::AIR));
+        assert_eq!(world.get_voxel(&IVec3::new(1, 1, 1)), Ok(Voxel::AIR));
+        assert_eq!(world.undo(), Ok(false));
+    }
+
+    #[test]
+    fn history_is_bounded_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0)]);
+        world.set_history_config(EditHistoryConfig { max_transactions: 2, max_changes: 10 });
+
+        for x in 0..5 {
+            world.set_voxel(&IVec3::new(x, 0, 0), Voxel::new(block::STONE)).unwrap();
+        }
+        assert_eq!(world.undo(), Ok(true));
+        assert_eq!(world.undo(), Ok(true));
+        assert_eq!(world.undo(), Ok(false));
+        assert_eq!(world.get_voxel(&IVec3::new(2, 0, 0)), Ok(Voxel::new(block::STONE)));
+
+        // Too big to keep at all
+        world.fill_box(&IVec3::new(0, 1, 0), &IVec3::new(10, 1, 0), Voxel::new(block::DIRT));
+        assert_eq!(world.undo(), Ok(false));
+    }
+
+    #[test]
+    fn redo_is_bounded_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0)]);
+        world.set_history_config(EditHistoryConfig { max_transactions: 3, max_changes: 10 });
+
+        for x in 0..3 {
+            world.set_voxel(&IVec3::new(x, 0, 0), Voxel::new(block::STONE)).unwrap();
+        }
+        for _ in 0..3 {
+            assert_eq!(world.undo(), Ok(true));
+        }
+        assert_eq!(world.undo(), Ok(false));
+
+        // Everything is on the redo stack, so that is what gets trimmed, keeping the next redos
+        world.set_history_config(EditHistoryConfig { max_transactions: 2, max_changes: 10 });
+        assert_eq!(world.redo(), Ok(true));
+        assert_eq!(world.redo(), Ok(true));
+        assert_eq!(world.redo(), Ok(false));
+        assert_eq!(world.get_voxel(&IVec3::new(1, 0, 0)), Ok(Voxel::new(block::STONE)));
+        assert_eq!(world.get_voxel(&IVec3::new(2, 0, 0)), Ok(Voxel::AIR));
+
+        // The change limit counts undone changes too
+        world.set_history_config(EditHistoryConfig { max_transactions: 2, max_changes: 1 });
+        assert_eq!(world.undo(), Ok(true));
+        assert_eq!(world.undo(), Ok(false));
+        assert_eq!(world.redo(), Ok(true));
+        assert_eq!(world.redo(), Ok(false));
+    }
+
+    #[test]
+    fn undo_into_unloaded_chunk_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0), IVec3::new(1, 0, 0)]);
+        world.set_voxel(&IVec3::new(20, 0, 0), Voxel::new(block::STONE)).unwrap();
+        world.remove_chunk(&IVec3::new(1, 0, 0));
+
+        assert!(world.undo().is_err());
+        // Still there once the chunk is back
+        assert!(world.history.can_undo());
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::fmt;
+use bevy::math::{IVec3, Vec3};
+use crate::chunk::Chunk;
+use crate::chunk_manager::ChunkManager;
+use crate::chunk_utils::{world_to_chunk_location, world_to_local_xyz};
+use crate::voxel::Voxel;
+use crate::voxel_world::history::{EditHistory, EditHistoryConfig, EditTransaction, VoxelChange};
+
+pub mod history;
+mod region_edits;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoxelWorldError {
+    // The chunk at this chunk location is not loaded, or is still being generated
+    ChunkNotLoaded(IVec3),
+}
+
+impl fmt::Display for VoxelWorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxelWorldError::ChunkNotLoaded(chunk_location) => write!(f, "chunk {} {} {} is not loaded",
+                                                                      chunk_location.x, chunk_location.y, chunk_location.z),
+        }
+    }
+}
+
+impl std::error::Error for VoxelWorldError {}
+
+fn neighbour_offsets() -> [IVec3; 6] {
+    [
+        IVec3::new(-1, 0, 0),
+        IVec3::new(1, 0, 0),
+        IVec3::new(0, -1, 0),
+        IVec3::new(0, 1, 0),
+        IVec3::new(0, 0, -1),
+        IVec3::new(0, 0, 1),
+    ]
+}
+
+// The local positions of the voxels on the face of a chunk that points towards offset
+fn face_voxels(chunk_size: &IVec3, offset: &IVec3) -> Vec<IVec3> {
+    let min = offset.max(IVec3::ZERO) * (*chunk_size - IVec3::ONE);
+    let max = min + (IVec3::ONE - offset.abs()) * (*chunk_size - IVec3::ONE);
+
+    let mut voxels = Vec::new();
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                voxels.push(IVec3::new(x, y, z));
+            }
+        }
+    }
+    voxels
+}
+
+// Owns every loaded chunk's voxel data, addressed in world space
+pub struct VoxelWorld {
+    pub chunk_manager: ChunkManager,
+    pub history: EditHistory,
+    chunks: HashMap<IVec3, Chunk>,
+}
+
+impl VoxelWorld {
+    pub fn new(chunk_manager: ChunkManager) -> VoxelWorld {
+        VoxelWorld {
+            chunk_manager,
+            history: EditHistory::new(EditHistoryConfig::default()),
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn chunk_size(&self) -> IVec3 {
+        self.chunk_manager.chunk_size
+    }
+
+    // Swaps border voxels with every loaded neighbour, replacing the generated border with the neighbour's real
+    // voxels. Either chunk is marked dirty if its border changed, e.g. the neighbour was edited before this loaded
+    pub fn insert_chunk(&mut self, mut chunk: Chunk) {
+        let chunk_size = self.chunk_size();
+
+        for offset in neighbour_offsets() {
+            if let Some(neighbour) = self.chunks.get_mut(&(chunk.location + offset)) {
+                for local_xyz in face_voxels(&chunk_size, &offset) {
+                    let outside_xyz = local_xyz + offset;
+                    chunk.set_border_voxel(&outside_xyz, neighbour.get_voxel(&(outside_xyz - offset * chunk_size)));
+                    neighbour.set_border_voxel(&(local_xyz - offset * chunk_size), chunk.get_voxel(&local_xyz));
+                }
+            }
+        }
+
+        self.chunks.insert(chunk.location, chunk);
+    }
+
+    pub fn remove_chunk(&mut self, chunk_location: &IVec3) -> Option<Chunk> {
+        self.chunks.remove(chunk_location)
+    }
+
+    pub fn get_chunk(&self, chunk_location: &IVec3) -> Option<&Chunk> {
+        self.chunks.get(chunk_location)
+    }
+
+    pub fn get_chunk_mut(&mut self, chunk_location: &IVec3) -> Option<&mut Chunk> {
+        self.chunks.get_mut(chunk_location)
+    }
+
+    pub fn is_chunk_loaded(&self, chunk_location: &IVec3) -> bool {
+        self.chunks.contains_key(chunk_location)
+    }
+
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn get_voxel(&self, world_xyz: &IVec3) -> Result<Voxel, VoxelWorldError> {
+        let chunk_size = self.chunk_size();
+        let chunk_location = world_to_chunk_location(world_xyz, &chunk_size);
+
+        match self.chunks.get(&chunk_location) {
+            Some(chunk) => Ok(chunk.get_voxel(&world_to_local_xyz(world_xyz, &chunk_size))),
+            None => Err(VoxelWorldError::ChunkNotLoaded(chunk_location))
+        }
+    }
+
+    // Returns the voxel that was replaced. The change is recorded for undo
+    pub fn set_voxel(&mut self, world_xyz: &IVec3, voxel: Voxel) -> Result<Voxel, VoxelWorldError> {
+        let previous = self.write_voxel(world_xyz, voxel)?;
+        if previous != voxel {
+            self.history.record(VoxelChange { world_xyz: *world_xyz, old: previous, new: voxel });
+        }
+        Ok(previous)
+    }
+
+    // Every edit made until end_edit_transaction is undone as one
+    pub fn begin_edit_transaction(&mut self) {
+        self.history.begin_transaction();
+    }
+
+    pub fn end_edit_transaction(&mut self) {
+        self.history.end_transaction();
+    }
+
+    pub fn set_history_config(&mut self, config: EditHistoryConfig) {
+        self.history.set_config(config);
+    }
+
+    // Returns false if there was nothing to undo. Fails without changing anything if the transaction touches a chunk
+    // that is not loaded
+    pub fn undo(&mut self) -> Result<bool, VoxelWorldError> {
+        match self.history.peek_undo() {
+            Some(transaction) => self.ensure_transaction_loaded(transaction)?,
+            None => return Ok(false)
+        }
+
+        let transaction = self.history.pop_undo().unwrap();
+        for change in transaction.changes.iter().rev() {
+            self.write_voxel(&change.world_xyz, change.old)?;
+        }
+        Ok(true)
+    }
+
+    pub fn redo(&mut self) -> Result<bool, VoxelWorldError> {
+        match self.history.peek_redo() {
+            Some(transaction) => self.ensure_transaction_loaded(transaction)?,
+            None => return Ok(false)
+        }
+
+        let transaction = self.history.pop_redo().unwrap();
+        for change in transaction.changes.iter() {
+            self.write_voxel(&change.world_xyz, change.new)?;
+        }
+        Ok(true)
+    }
+
+    fn ensure_transaction_loaded(&self, transaction: &EditTransaction) -> Result<(), VoxelWorldError> {
+        let chunk_size = self.chunk_size();
+        for change in transaction.changes.iter() {
+            let chunk_location = world_to_chunk_location(&change.world_xyz, &chunk_size);
+            if !self.chunks.contains_key(&chunk_location) {
+                return Err(VoxelWorldError::ChunkNotLoaded(chunk_location));
+            }
+        }
+        Ok(())
+    }
+
+    // The edit path shared by set_voxel, undo and redo. The owning chunk is marked dirty, and the neighbours which
+    // share a face with the voxel have their border updated so that their border faces are rebuilt
+    fn write_voxel(&mut self, world_xyz: &IVec3, voxel: Voxel) -> Result<Voxel, VoxelWorldError> {
+        let chunk_size = self.chunk_size();
+        let chunk_location = world_to_chunk_location(world_xyz, &chunk_size);
+        let local_xyz = world_to_local_xyz(world_xyz, &chunk_size);
+
+        let chunk = match self.chunks.get_mut(&chunk_location) {
+            Some(chunk) => chunk,
+            None => return Err(VoxelWorldError::ChunkNotLoaded(chunk_location))
+        };
+
+        let previous = chunk.set_voxel(&local_xyz, voxel);
+
+        if previous != voxel && chunk.is_on_border(&local_xyz) {
+            self.update_neighbour_borders(world_xyz, voxel);
+        }
+
+        Ok(previous)
+    }
+
+    // Copies a changed voxel on a chunk border into the border of the chunks that share a face with it
+    fn update_neighbour_borders(&mut self, world_xyz: &IVec3, voxel: Voxel) {
+        let chunk_size = self.chunk_size();
+        let chunk_location = world_to_chunk_location(world_xyz, &chunk_size);
+
+        for offset in neighbour_offsets() {
+            let neighbour_location = world_to_chunk_location(&(*world_xyz + offset), &chunk_size);
+            if neighbour_location != chunk_location {
+                if let Some(neighbour) = self.chunks.get_mut(&neighbour_location) {
+                    neighbour.set_border_voxel(&(*world_xyz - neighbour_location * chunk_size), voxel);
+                }
+            }
+        }
+    }
+
+    // Clears the dirty flag of every dirty chunk and returns their locations
+    pub fn take_dirty_chunks(&mut self) -> Vec<IVec3> {
+        let mut dirty_chunks = Vec::new();
+        for chunk in self.chunks.values_mut() {
+            if chunk.dirty {
+                chunk.dirty = false;
+                dirty_chunks.push(chunk.location);
+            }
+        }
+        dirty_chunks
+    }
+
+    // The voxel containing a point in world space, e.g. the camera's translation
+    pub fn get_voxel_at_position(&self, position: &Vec3) -> Result<Voxel, VoxelWorldError> {
+        self.get_voxel(&position.floor().as_ivec3())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::block;
+    use crate::block::BlockRegistry;
+    use crate::chunk::Chunk;
+    use crate::chunk_manager::{ChunkManager, ChunkMaterials};
+    use crate::generation::superflat::SuperflatGenerator;
+    use crate::voxel::Voxel;
+    use crate::voxel_world::{VoxelWorld, VoxelWorldError};
+    use crate::world_settings::WorldSettings;
+    use bevy::math::IVec3;
+    use bevy::math::Vec3;
+
+    // 16^3 chunks of air
+    pub fn test_world(loaded_chunks: &[IVec3]) -> VoxelWorld {
+        let world_settings = WorldSettings {
+            chunk_size: IVec3::new(16, 16, 16),
+            ..Default::default()
+        };
+        let chunk_size = world_settings.chunk_size;
+        let mut world = VoxelWorld::new(ChunkManager::new(
+            &world_settings,
+            ChunkMaterials::default(),
+            Arc::new(BlockRegistry::default()),
+            Arc::new(SuperflatGenerator::default())));
+
+        for chunk_location in loaded_chunks {
+            world.insert_chunk(Chunk::filled(chunk_size, *chunk_location, Voxel::AIR));
+        }
+
+        world
+    }
+
+    #[test]
+    fn get_and_set_voxel_across_chunks_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0), IVec3::new(-1, 0, 0), IVec3::new(-1, -1, -1)]);
+        let stone = Voxel::new(block::STONE);
+
+        assert_eq!(world.set_voxel(&IVec3::new(-1, 0, 0), stone), Ok(Voxel::AIR));
+        assert_eq!(world.set_voxel(&IVec3::new(-16, -16, -16), stone), Ok(Voxel::AIR));
+        assert_eq!(world.set_voxel(&IVec3::new(15, 15, 15), stone), Ok(Voxel::AIR));
+
+        assert_eq!(world.get_voxel(&IVec3::new(-1, 0, 0)), Ok(stone));
+        assert_eq!(world.get_voxel(&IVec3::new(0, 0, 0)), Ok(Voxel::AIR));
+        assert_eq!(world.get_voxel(&IVec3::new(-16, -16, -16)), Ok(stone));
+        assert_eq!(world.get_voxel(&IVec3::new(15, 15, 15)), Ok(stone));
+        assert_eq!(world.get_voxel_at_position(&Vec3::new(-0.5, 0.5, 0.5)), Ok(stone));
+
+        assert_eq!(world.get_chunk(&IVec3::new(-1, 0, 0)).unwrap().get_voxel(&IVec3::new(15, 0, 0)), stone);
+    }
+
+    #[test]
+    fn set_voxel_marks_border_neighbours_dirty_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0), IVec3::new(-1, 0, 0), IVec3::new(0, 1, 0)]);
+        let stone = Voxel::new(block::STONE);
+
+        world.set_voxel(&IVec3::new(5, 5, 5), stone).unwrap();
+        assert_eq!(world.take_dirty_chunks(), vec![IVec3::new(0, 0, 0)]);
+        assert!(world.take_dirty_chunks().is_empty());
+
+        // Writing the same voxel again is not a change
+        world.set_voxel(&IVec3::new(5, 5, 5), stone).unwrap();
+        assert!(world.take_dirty_chunks().is_empty());
+
+        world.set_voxel(&IVec3::new(0, 15, 3), stone).unwrap();
+        let mut dirty_chunks = world.take_dirty_chunks();
+        dirty_chunks.sort_by_key(|c| (c.x, c.y, c.z));
+        assert_eq!(dirty_chunks, vec![IVec3::new(-1, 0, 0), IVec3::new(0, 0, 0), IVec3::new(0, 1, 0)]);
+    }
+
+    #[test]
+    fn edits_update_neighbour_borders_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0), IVec3::new(0, 1, 0)]);
+        let stone = Voxel::new(block::STONE);
+
+        world.set_voxel(&IVec3::new(4, 15, 4), stone).unwrap();
+        assert_eq!(world.get_chunk(&IVec3::new(0, 1, 0)).unwrap().get_voxel_or_border(&IVec3::new(4, -1, 4)), stone);
+
+        // A chunk loaded after its neighbour was edited picks up the edit, and hands over its own voxels
+        world.set_voxel(&IVec3::new(15, 2, 3), stone).unwrap();
+        world.take_dirty_chunks();
+        let mut chunk = Chunk::filled(world.chunk_size(), IVec3::new(1, 0, 0), Voxel::AIR);
+        chunk.set_voxel(&IVec3::new(0, 5, 5), stone);
+        chunk.dirty = false;
+        world.insert_chunk(chunk);
+
+        let inserted = world.get_chunk(&IVec3::new(1, 0, 0)).unwrap();
+        assert_eq!(inserted.get_voxel_or_border(&IVec3::new(-1, 2, 3)), stone);
+        assert_eq!(inserted.get_voxel_or_border(&IVec3::new(-1, 2, 4)), Voxel::AIR);
+        assert_eq!(world.get_chunk(&IVec3::new(0, 0, 0)).unwrap().get_voxel_or_border(&IVec3::new(16, 5, 5)), stone);
+
+        let mut dirty_chunks = world.take_dirty_chunks();
+        dirty_chunks.sort_by_key(|c| (c.x, c.y, c.z));
+        assert_eq!(dirty_chunks, vec![IVec3::new(0, 0, 0), IVec3::new(1, 0, 0)]);
+    }
+
+    #[test]
+    fn unloaded_chunk_is_an_error_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0)]);
+        assert_eq!(world.get_voxel(&IVec3::new(0, -1, 0)), Err(VoxelWorldError::ChunkNotLoaded(IVec3::new(0, -1, 0))));
+        assert_eq!(world.set_voxel(&IVec3::new(16, 0, 0), Voxel::AIR), Err(VoxelWorldError::ChunkNotLoaded(IVec3::new(1, 0, 0))));
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::math::IVec3;
+use crate::chunk_utils::{local_to_world_xyz, world_to_chunk_location};
+use crate::voxel::Voxel;
+use crate::voxel_world::history::VoxelChange;
+use crate::voxel_world::VoxelWorld;
+
+// Bulk edits. Each one visits the chunks it overlaps one at a time, edited chunks are only marked dirty so they get a
+// single remesh no matter how many of their voxels changed. Voxels in chunks that are not loaded are skipped.
+// Every edit returns how many voxels actually changed, and is undone as a single transaction
+impl VoxelWorld {
+    // min and max are inclusive
+    pub fn fill_box(&mut self, min: &IVec3, max: &IVec3, voxel: Voxel) -> usize {
+        self.edit_region(min, max, |_world_xyz, _current| Some(voxel))
+    }
+
+    pub fn fill_sphere(&mut self, center: &IVec3, radius: f32, voxel: Voxel) -> usize {
+        let extent = IVec3::splat(radius.ceil() as i32);
+        let radius_squared = radius * radius;
+
+        self.edit_region(&(*center - extent), &(*center + extent), |world_xyz, _current| {
+            if (*world_xyz - *center).as_vec3().length_squared() <= radius_squared {
+                Some(voxel)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn carve_sphere(&mut self, center: &IVec3, radius: f32) -> usize {
+        self.fill_sphere(center, radius, Voxel::AIR)
+    }
+
+    // min and max are inclusive
+    pub fn replace_in_box(&mut self, min: &IVec3, max: &IVec3, from: Voxel, to: Voxel) -> usize {
+        self.edit_region(min, max, |_world_xyz, current| {
+            if current == from {
+                Some(to)
+            } else {
+                None
+            }
+        })
+    }
+
+    // Calls edit for every loaded voxel in the box, a returned voxel replaces the current one
+    fn edit_region<F>(&mut self, min: &IVec3, max: &IVec3, mut edit: F) -> usize
+        where F: FnMut(&IVec3, Voxel) -> Option<Voxel> {
+        let chunk_size = self.chunk_size();
+        let (min, max) = (min.min(*max), min.max(*max));
+        let min_chunk = world_to_chunk_location(&min, &chunk_size);
+        let max_chunk = world_to_chunk_location(&max, &chunk_size);
+
+        let mut changes = Vec::new();
+        let mut changed_border_voxels = Vec::new();
+
+        for chunk_x in min_chunk.x..=max_chunk.x {
+            for chunk_y in min_chunk.y..=max_chunk.y {
+                for chunk_z in min_chunk.z..=max_chunk.z {
+                    let chunk_location = IVec3::new(chunk_x, chunk_y, chunk_z);
+                    let chunk = match self.get_chunk_mut(&chunk_location) {
+                        Some(chunk) => chunk,
+                        None => continue
+                    };
+
+                    // The part of the box inside this chunk, in local space
+                    let chunk_origin = chunk_location * chunk_size;
+                    let local_min = (min - chunk_origin).max(IVec3::ZERO);
+                    let local_max = (max - chunk_origin).min(chunk_size - IVec3::ONE);
+
+                    for x in local_min.x..=local_max.x {
+                        for y in local_min.y..=local_max.y {
+                            for z in local_min.z..=local_max.z {
+                                let local_xyz = IVec3::new(x, y, z);
+                                let world_xyz = local_to_world_xyz(&chunk_location, &local_xyz, &chunk_size);
+                                let current = chunk.get_voxel(&local_xyz);
+
+                                if let Some(voxel) = edit(&world_xyz, current) {
+                                    if voxel != current {
+                                        chunk.set_voxel(&local_xyz, voxel);
+                                        changes.push(VoxelChange { world_xyz, old: current, new: voxel });
+
+                                        if chunk.is_on_border(&local_xyz) {
+                                            changed_border_voxels.push((world_xyz, voxel));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (world_xyz, voxel) in changed_border_voxels {
+            self.update_neighbour_borders(&world_xyz, voxel);
+        }
+
+        let changed = changes.len();
+        self.history.begin_transaction();
+        for change in changes {
+            self.history.record(change);
+        }
+        self.history.end_transaction();
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block;
+    use crate::voxel::Voxel;
+    use crate::voxel_world::tests::test_world;
+    use bevy::math::IVec3;
+
+    fn all_chunks() -> Vec<IVec3> {
+        let mut chunks = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    chunks.push(IVec3::new(x, y, z));
+                }
+            }
+        }
+        chunks
+    }
+
+    #[test]
+    fn fill_box_across_chunks_test() {
+        let mut world = test_world(&all_chunks());
+        let stone = Voxel::new(block::STONE);
+
+        // 10 x 4 x 3 box straddling x = 0 and z = 0
+        assert_eq!(world.fill_box(&IVec3::new(-5, 2, -1), &IVec3::new(4, 5, 1), stone), 120);
+        assert_eq!(world.fill_box(&IVec3::new(-5, 2, -1), &IVec3::new(4, 5, 1), stone), 0);
+        assert_eq!(world.get_voxel(&IVec3::new(-5, 2, -1)), Ok(stone));
+        assert_eq!(world.get_voxel(&IVec3::new(4, 5, 1)), Ok(stone));
+        assert_eq!(world.get_voxel(&IVec3::new(5, 5, 1)), Ok(Voxel::AIR));
+
+        // Only the four chunks holding the box, the faces it touches are shared between those four
+        assert_eq!(world.take_dirty_chunks().len(), 4);
+    }
+
+    #[test]
+    fn fill_box_corners_in_any_order_test() {
+        let stone = Voxel::new(block::STONE);
+        let corners = [
+            (IVec3::new(4, 5, 1), IVec3::new(-5, 2, -1)),
+            (IVec3::new(4, 2, 1), IVec3::new(-5, 5, -1)),
+            (IVec3::new(-5, 5, 1), IVec3::new(4, 2, -1)),
+        ];
+        for (a, b) in corners {
+            let mut world = test_world(&all_chunks());
+            assert_eq!(world.fill_box(&a, &b, stone), 120);
+            assert_eq!(world.get_voxel(&IVec3::new(-5, 2, -1)), Ok(stone));
+            assert_eq!(world.get_voxel(&IVec3::new(4, 5, 1)), Ok(stone));
+        }
+    }
+
+    #[test]
+    fn fill_box_skips_unloaded_chunks_test() {
+        let mut world = test_world(&[IVec3::new(0, 0, 0)]);
+        assert_eq!(world.fill_box(&IVec3::new(-2, 0, 0), &IVec3::new(1, 0, 0), Voxel::new(block::STONE)), 2);
+    }
+
+    #[test]
+    fn sphere_and_replace_test() {
+        let mut world = test_world(&all_chunks());
+        let stone = Voxel::new(block::STONE);
+        let dirt = Voxel::new(block::DIRT);
+
+        // Radius 1 is the center and its 6 face neighbours
+        assert_eq!(world.fill_sphere(&IVec3::new(0, 0, 0), 1.0, stone), 7);
+        assert_eq!(world.fill_sphere(&IVec3::new(0, 0, 0), 2.0, stone), 33 - 7);
+
+        // Everything but the 10 voxels above y = 0
+        assert_eq!(world.replace_in_box(&IVec3::new(-2, -2, -2), &IVec3::new(2, 0, 2), stone, dirt), 23);
+        assert_eq!(world.get_voxel(&IVec3::new(0, -2, 0)), Ok(dirt));
+        assert_eq!(world.get_voxel(&IVec3::new(0, 2, 0)), Ok(stone));
+
+        assert_eq!(world.carve_sphere(&IVec3::new(0, 0, 0), 2.0), 33);
+        assert_eq!(world.get_voxel(&IVec3::new(0, 2, 0)), Ok(Voxel::AIR));
+    }
+}
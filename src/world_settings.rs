@@ -0,0 +1,180 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use bevy::math::{IVec3, Vec3};
+use serde::{Deserialize, Serialize};
+use crate::generation::heightmap::HeightmapSettings;
+use crate::generation::TerrainGeneratorKind;
+
+// Everything needed to reproduce a world. Loaded from a TOML file (any missing key keeps its default) and then
+// overridden from the command line, e.g.
+// cargo run --release -- --config world.toml --seed 42 --render-distance 6
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldSettings {
+    pub seed: i64,
+    pub generator: TerrainGeneratorKind,
+    // Directory of biome .toml files used by the noise generator
+    pub biomes_dir: PathBuf,
+    // Whether the noise generator wears its terrain down with hydraulic and thermal erosion
+    pub erosion: bool,
+    // Used by the heightmap generator
+    pub heightmap: HeightmapSettings,
+    pub chunk_size: IVec3,
+    // In chunks, horizontally
+    pub render_distance: i32,
+    // In chunks, how many layers are loaded below and above the camera's chunk
+    pub render_distance_down: i32,
+    pub render_distance_up: i32,
+    pub spawn_point: Vec3,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        WorldSettings {
+            seed: 883_279_212_983_182_319,
+            generator: TerrainGeneratorKind::Noise,
+            biomes_dir: PathBuf::from("assets/biomes"),
+            erosion: false,
+            heightmap: HeightmapSettings::default(),
+            chunk_size: IVec3::new(32, 32, 32),
+            render_distance: 8,
+            render_distance_down: 2,
+            render_distance_up: 2,
+            spawn_point: Vec3::new(32.0, 64.0, 32.0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WorldSettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    InvalidArgument(String),
+}
+
+impl fmt::Display for WorldSettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldSettingsError::Io(error) => write!(f, "could not read world settings: {}", error),
+            WorldSettingsError::Parse(error) => write!(f, "could not parse world settings: {}", error),
+            WorldSettingsError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for WorldSettingsError {}
+
+impl WorldSettings {
+    pub fn from_toml(toml_str: &str) -> Result<WorldSettings, WorldSettingsError> {
+        toml::from_str(toml_str).map_err(WorldSettingsError::Parse)
+    }
+
+    pub fn load(path: &Path) -> Result<WorldSettings, WorldSettingsError> {
+        let toml_str = std::fs::read_to_string(path).map_err(WorldSettingsError::Io)?;
+        WorldSettings::from_toml(&toml_str)
+    }
+
+    // Arguments are --config <path>, --seed <n>, --generator <noise|superflat|checkerboard|sparse|heightmap>,
+    // --biomes <dir>, --erosion <true|false>, --heightmap <png>, --chunk-size <x,y,z>, --render-distance <n>,
+    // --render-distance-y <down,up> and --spawn <x,y,z>. The config file is applied first wherever it appears
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<WorldSettings, WorldSettingsError> {
+        let mut options = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args.next()
+                .ok_or_else(|| WorldSettingsError::InvalidArgument(format!("{} is missing a value", flag)))?;
+            options.push((flag, value));
+        }
+
+        let mut settings = match options.iter().find(|(flag, _)| flag == "--config") {
+            Some((_, path)) => WorldSettings::load(Path::new(path))?,
+            None => WorldSettings::default()
+        };
+
+        for (flag, value) in options.iter() {
+            match flag.as_str() {
+                "--config" => {},
+                "--seed" => settings.seed = parse_value(flag, value)?,
+                "--generator" => settings.generator = parse_value(flag, value)?,
+                "--biomes" => settings.biomes_dir = PathBuf::from(value),
+                "--erosion" => settings.erosion = parse_value(flag, value)?,
+                "--heightmap" => settings.heightmap.path = PathBuf::from(value),
+                "--chunk-size" => {
+                    let [x, y, z] = parse_list::<i32, 3>(flag, value)?;
+                    settings.chunk_size = IVec3::new(x, y, z);
+                },
+                "--render-distance" => settings.render_distance = parse_value(flag, value)?,
+                "--render-distance-y" => {
+                    let [down, up] = parse_list::<i32, 2>(flag, value)?;
+                    settings.render_distance_down = down;
+                    settings.render_distance_up = up;
+                },
+                "--spawn" => {
+                    let [x, y, z] = parse_list::<f32, 3>(flag, value)?;
+                    settings.spawn_point = Vec3::new(x, y, z);
+                },
+                _ => return Err(WorldSettingsError::InvalidArgument(format!("unknown option {}", flag)))
+            }
+        }
+
+        if settings.chunk_size.min_element() <= 0 {
+            return Err(WorldSettingsError::InvalidArgument("chunk size must be positive".to_string()));
+        }
+
+        Ok(settings)
+    }
+}
+
+pub fn parse_value<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, WorldSettingsError> {
+    value.trim().parse()
+        .map_err(|_| WorldSettingsError::InvalidArgument(format!("{} has an invalid value {}", flag, value)))
+}
+
+pub fn parse_list<T: std::str::FromStr + Copy + Default, const N: usize>(flag: &str, value: &str) -> Result<[T; N], WorldSettingsError> {
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() != N {
+        return Err(WorldSettingsError::InvalidArgument(format!("{} expects {} comma separated values", flag, N)));
+    }
+
+    let mut list = [T::default(); N];
+    for (i, part) in parts.iter().enumerate() {
+        list[i] = parse_value(flag, part)?;
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generation::TerrainGeneratorKind;
+    use crate::world_settings::WorldSettings;
+    use bevy::math::IVec3;
+    use bevy::math::Vec3;
+
+    #[test]
+    fn partial_toml_keeps_defaults_test() {
+        let settings = WorldSettings::from_toml("seed = 42\nchunk_size = [16, 32, 16]\n").unwrap();
+        assert_eq!(settings.seed, 42);
+        assert_eq!(settings.chunk_size, IVec3::new(16, 32, 16));
+        assert_eq!(settings.render_distance, WorldSettings::default().render_distance);
+    }
+
+    #[test]
+    fn command_line_overrides_test() {
+        let args = ["--seed", "7", "--spawn", "1.5,2,-3", "--render-distance-y", "1,3", "--generator", "superflat"];
+        let settings = WorldSettings::from_args(args.iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(settings.seed, 7);
+        assert_eq!(settings.generator, TerrainGeneratorKind::Superflat);
+        assert_eq!(settings.spawn_point, Vec3::new(1.5, 2.0, -3.0));
+        assert_eq!((settings.render_distance_down, settings.render_distance_up), (1, 3));
+
+        assert!(WorldSettings::from_args(["--seed".to_string()]).is_err());
+        assert!(WorldSettings::from_args(["--bogus".to_string(), "1".to_string()]).is_err());
+        assert!(WorldSettings::from_args(["--chunk-size".to_string(), "0,32,32".to_string()]).is_err());
+    }
+
+    #[test]
+    fn shipped_world_toml_parses_test() {
+        let settings = WorldSettings::from_toml(include_str!("../world.toml")).unwrap();
+        assert_eq!(settings, WorldSettings::default());
+    }
+}